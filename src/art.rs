@@ -0,0 +1,333 @@
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    env,
+    ffi::OsString,
+    fs,
+    hash::{Hash, Hasher},
+    io,
+    os::unix::ffi::OsStringExt,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    sync::mpsc::{self, Receiver},
+    thread,
+    time::SystemTime,
+};
+
+/// How big the art cache can get before the least recently used images are
+/// evicted, in bytes.
+const CACHE_SIZE: u64 = 32 * 1024 * 1024;
+
+/// Where a track's art comes from.
+#[derive(Debug, PartialEq)]
+enum ArtSource<'a> {
+    File(PathBuf),
+    /// Base64 encoded image data.
+    Data(&'a str),
+}
+
+/// Keeps local copies of album art, so widgets that can only show images from
+/// a file can show it.
+pub struct ArtCache {
+    dir: PathBuf,
+    /// The track id and art URL the paths below belong to.
+    track: Option<(String, Option<String>)>,
+    /// Cached images by size, 0 meaning the original size.
+    paths: HashMap<u8, PathBuf>,
+    /// Images of the current track being cached in the background.
+    pending: Option<Receiver<(u8, PathBuf)>>,
+    /// Whether images are cached on another thread, since copying and
+    /// resizing them would stall the bar on track changes.
+    background: bool,
+}
+
+impl ArtCache {
+    pub fn new() -> Self {
+        let cache_home = env::var_os("XDG_CACHE_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".cache")))
+            .unwrap_or_else(env::temp_dir);
+
+        Self {
+            dir: cache_home.join("cornetroll").join("art"),
+            track: None,
+            paths: HashMap::new(),
+            pending: None,
+            background: true,
+        }
+    }
+
+    /// Caches images before returning instead, for when there's only one
+    /// chance to show them, like with `--once`.
+    pub fn set_background(&mut self, background: bool) {
+        self.background = background;
+    }
+
+    /// Caches the art of the current track in every size given, if it
+    /// changed since the last time. The paths show up as they're cached.
+    pub fn update(&mut self, track_id: &str, url: Option<&str>, sizes: &[u8]) {
+        let track = (track_id.to_string(), url.map(str::to_string));
+
+        if self.track.as_ref() != Some(&track) {
+            self.paths.clear();
+            self.pending = url.map(|url| self.spawn(track_id, url, sizes));
+            self.track = Some(track);
+        }
+
+        if let Some(pending) = &self.pending {
+            self.paths.extend(pending.try_iter());
+        }
+    }
+
+    fn spawn(&self, track_id: &str, url: &str, sizes: &[u8]) -> Receiver<(u8, PathBuf)> {
+        let (sender, receiver) = mpsc::channel();
+        let (dir, track_id, url, sizes) = (self.dir.clone(), track_id.to_string(), url.to_string(), sizes.to_vec());
+
+        let job = move || for size in sizes {
+            if let Ok(Some(path)) = cache(&dir, &track_id, &url, size) {
+                // Nobody's listening once the track changes
+                if sender.send((size, path)).is_err() {
+                    break;
+                }
+            }
+        };
+
+        if self.background {
+            thread::spawn(job);
+        } else {
+            job();
+        }
+
+        receiver
+    }
+
+    /// The cached art of the current track.
+    pub fn path(&self, size: u8) -> Option<&Path> {
+        self.paths.get(&size).map(PathBuf::as_path)
+    }
+}
+
+/// Copies a track's art into the cache, resized to fit in `size` unless it's 0.
+fn cache(dir: &Path, track_id: &str, url: &str, size: u8) -> io::Result<Option<PathBuf>> {
+    let (source, extension) = match parse_art_url(url) {
+        Some(v) => v,
+        None => return Ok(None),
+    };
+
+    let mut hasher = DefaultHasher::new();
+    (track_id, url, size).hash(&mut hasher);
+    let key = hasher.finish();
+    let path = dir.join(format!("{:016x}.{}", key, extension));
+
+    // Already cached, mark it as recently used
+    if path.exists() {
+        fs::File::options().append(true).open(&path)?.set_modified(SystemTime::now())?;
+        return Ok(Some(path));
+    }
+
+    // Widgets might read it while it's being written otherwise
+    fs::create_dir_all(dir)?;
+    let partial = dir.join(format!("{:016x}.part.{}", key, extension));
+
+    match source {
+        ArtSource::File(source) => { fs::copy(source, &partial)?; },
+        ArtSource::Data(data) => match decode_base64(data) {
+            Some(data) => fs::write(&partial, data)?,
+            None => return Ok(None),
+        },
+    }
+
+    // Keep the original size when ImageMagick isn't available
+    if size > 0 {
+        resize(&partial, size);
+    }
+
+    fs::rename(&partial, &path)?;
+
+    // The image is there either way, and another instance might be evicting
+    // at the same time
+    let _ = evict(dir, &path);
+    Ok(Some(path))
+}
+
+/// Removes the least recently used images until the cache fits in
+/// `CACHE_SIZE`.
+fn evict(dir: &Path, keep: &Path) -> io::Result<()> {
+    let mut files: Vec<(SystemTime, u64, PathBuf)> = fs::read_dir(dir)?
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let meta = entry.metadata().ok()?;
+
+            if meta.is_file() {
+                Some((meta.modified().ok()?, meta.len(), entry.path()))
+            } else {
+                None
+            }
+        })
+        .collect();
+
+    let mut total: u64 = files.iter().map(|(_, len, _)| len).sum();
+    files.sort();
+
+    for (_, len, path) in files {
+        if total <= CACHE_SIZE {
+            break;
+        } else if path != keep && (fs::remove_file(&path).is_ok() || !path.exists()) {
+            total -= len;
+        }
+    }
+
+    Ok(())
+}
+
+/// Splits an art URL into its source and the extension for the cached file.
+/// Only `file://` URLs and base64 `data:` URIs are supported.
+fn parse_art_url(url: &str) -> Option<(ArtSource<'_>, String)> {
+    if let Some(path) = file_url_path(url) {
+        let extension = path.extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or("img")
+            .to_ascii_lowercase();
+
+        Some((ArtSource::File(path), extension))
+    } else if let Some(uri) = url.strip_prefix("data:") {
+        let (media_type, data) = uri.split_once(',')?;
+        let mime = media_type.strip_suffix(";base64")?;
+
+        let extension = match mime.strip_prefix("image/")? {
+            "jpeg" => "jpg",
+            "svg+xml" => "svg",
+            subtype => subtype,
+        };
+
+        Some((ArtSource::Data(data), extension.to_string()))
+    } else {
+        None
+    }
+}
+
+/// The local path of a `file://` URL.
+pub fn file_url_path(url: &str) -> Option<PathBuf> {
+    let path = url.strip_prefix("file://")?;
+    Some(PathBuf::from(OsString::from_vec(percent_decode(path))))
+}
+
+/// Decodes `%XX` escapes, as in `file:///music/My%20Album/cover.jpg`.
+fn percent_decode(value: &str) -> Vec<u8> {
+    let bytes = value.as_bytes();
+    let mut output = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let escaped = bytes.get(i+1..i+3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+
+        match (bytes[i], escaped) {
+            (b'%', Some(byte)) => {
+                output.push(byte);
+                i += 3;
+            },
+            (byte, _) => {
+                output.push(byte);
+                i += 1;
+            },
+        }
+    }
+
+    output
+}
+
+/// Decodes standard or URL-safe base64, with or without padding.
+fn decode_base64(input: &str) -> Option<Vec<u8>> {
+    let mut output = Vec::with_capacity(input.len() * 3 / 4);
+    let mut buffer = 0u32;
+    let mut bits = 0;
+
+    for c in input.bytes() {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' | b'-' => 62,
+            b'/' | b'_' => 63,
+            b'=' => break,
+            c if c.is_ascii_whitespace() => continue,
+            _ => return None,
+        };
+
+        buffer = (buffer << 6 | value as u32) & 0xffff;
+        bits += 6;
+
+        if bits >= 8 {
+            bits -= 8;
+            output.push((buffer >> bits) as u8);
+        }
+    }
+
+    Some(output)
+}
+
+/// Resizes an image in place with ImageMagick, if it's installed.
+fn resize(path: &Path, size: u8) -> bool {
+    let geometry = format!("{0}x{0}", size);
+
+    ["magick", "convert"].iter().any(|program| {
+        Command::new(program)
+            .arg(path)
+            .args(["-resize", &geometry])
+            .arg(path)
+            // Anything printed would end up in the bar
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .map(|status| status.success())
+            .unwrap_or(false)
+    })
+}
+
+#[test]
+fn test_art_url() {
+    assert_eq!(
+        parse_art_url("file:///music/My%20Album/Cover.JPG"),
+        Some((ArtSource::File(PathBuf::from("/music/My Album/Cover.JPG")), "jpg".to_string())),
+    );
+    assert_eq!(
+        parse_art_url("data:image/jpeg;base64,/9j/"),
+        Some((ArtSource::Data("/9j/"), "jpg".to_string())),
+    );
+    assert_eq!(parse_art_url("data:image/png,raw"), None);
+    assert_eq!(parse_art_url("https://example.com/cover.jpg"), None);
+
+    assert_eq!(percent_decode("100%25%zz"), b"100%%zz");
+    assert_eq!(decode_base64("aGVsbG8gd29ybGQ=").unwrap(), b"hello world");
+    assert_eq!(decode_base64("aGk").unwrap(), b"hi");
+    assert_eq!(decode_base64("a*b"), None);
+}
+
+#[test]
+fn test_art_cache() {
+    let dir = env::temp_dir().join(format!("cornetroll-art-{}", std::process::id()));
+    let source = dir.join("Cover Art.png");
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(&source, b"png").unwrap();
+    let url = format!("file://{}", source.display()).replace(' ', "%20");
+
+    let mut cache = ArtCache { dir: dir.join("cache"), ..ArtCache::new() };
+    cache.update("track", Some(&url), &[0]);
+    for _ in 0..100 {
+        if cache.path(0).is_some() { break; }
+        thread::sleep(std::time::Duration::from_millis(10));
+        cache.update("track", Some(&url), &[0]);
+    }
+    let path = cache.path(0).expect("cached in the background").to_path_buf();
+    assert_eq!(fs::read(&path).unwrap(), b"png");
+
+    cache.set_background(false);
+    cache.update("other", Some(&url), &[0]);
+    assert!(cache.path(0).is_some_and(|other| other != path));
+    cache.update("none", None, &[0]);
+    assert_eq!(cache.path(0), None);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -1,23 +1,143 @@
-use std::fmt;
+use std::{
+    cmp::Ordering,
+    fmt,
+    mem,
+};
+use super::pattern::Pattern;
 use super::{
+    check_command_argument,
+    COMMANDS,
+    DEFAULT_ART_SETTINGS,
     DEFAULT_INFO_SETTINGS,
     DEFAULT_META_SETTINGS,
+    DEFAULT_PLAYERS_SETTINGS,
+    DEFAULT_LYRICS_SETTINGS,
+    DEFAULT_PROGRESS_SETTINGS,
+    DEFAULT_SEEK_SETTINGS,
     DEFAULT_TIME_SETTINGS,
+    DEFAULT_VOLUME_SETTINGS,
 };
 
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MouseButton {
+    Left = 1,
+    Middle = 2,
+    Right = 3,
+    ScrollUp = 4,
+    ScrollDown = 5,
+}
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum DisplayFormat {
-    Prev,
-    Next,
-    PlayPause,
-    Status,
+    /// Blocks with icons take overrides for the icon theme's, e.g. `[prev:<]`
+    Prev(Option<String>),
+    Next(Option<String>),
+    /// `(play_icon, pause_icon)`
+    PlayPause(Option<String>, Option<String>),
+    Stop(Option<String>),
+    Raise(Option<String>),
+    Quit(Option<String>),
+    /// `(play_icon, pause_icon, stopped_icon)`
+    Status(Option<String>, Option<String>, Option<String>),
     /// `(show number of players, show name)`
     PlayerInfo(bool, bool),
-    /// `(buffer_size, scroll_timeout)`
-    Metadata(u8, u8),
+    /// `(buffer_size, scroll_timeout, name)`, named like `[metadata#title]`
+    Metadata(u8, u8, Option<String>),
     /// `(show_length, use_remaining)`
     Time(bool, bool),
+    /// `(show_icon, show_percentage)`
+    Volume(bool, bool),
+    /// `(width, style)`
+    Progress(u8, String),
+    /// `(on_icon, off_icon)`
+    Shuffle(Option<String>, Option<String>),
+    /// `(none_icon, track_icon, playlist_icon)`
+    Loop(Option<String>, Option<String>, Option<String>),
+    /// `(seconds, icon)`
+    SeekBack(u8, Option<String>),
+    /// `(seconds, icon)`
+    SeekForward(u8, Option<String>),
+    /// `(size)`
+    Art(u8),
+    /// `(buffer_size, scroll_timeout)`
+    Lyrics(u8, u8),
+    /// `(name_size, show_status)`
+    Players(u8, bool),
     String(String),
+    /// A block with its own mouse bindings, e.g. `[info;scroll-up=next-player]`
+    Clickable(Box<DisplayFormat>, Vec<(MouseButton, String)>),
+    /// A section only shown when its condition is met, e.g. `{playing:[time]}`
+    Conditional(Condition, Vec<DisplayFormat>),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Condition {
+    Playing,
+    Paused,
+    Stopped,
+    /// The track has a tag, e.g. `has:album`
+    Has(String),
+    /// How many players there are compared to a number, e.g. `players>1`
+    Players(Ordering, usize),
+}
+
+impl DisplayFormat {
+    /// The block itself, without any bindings.
+    pub fn block(&self) -> &DisplayFormat {
+        match self {
+            DisplayFormat::Clickable(block, _) => block,
+            block => block,
+        }
+    }
+}
+
+/// Every block in a display format, including the ones in conditional sections.
+pub fn all_blocks(format: &[DisplayFormat]) -> Vec<&DisplayFormat> {
+    let mut blocks = Vec::new();
+
+    for block in format {
+        match block {
+            DisplayFormat::Conditional(_, section) => blocks.extend(all_blocks(section)),
+            block => blocks.push(block),
+        }
+    }
+
+    blocks
+}
+
+impl MouseButton {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "left" => Some(MouseButton::Left),
+            "middle" => Some(MouseButton::Middle),
+            "right" => Some(MouseButton::Right),
+            "scroll-up" => Some(MouseButton::ScrollUp),
+            "scroll-down" => Some(MouseButton::ScrollDown),
+            _ => None,
+        }
+    }
+
+    /// Buttons as numbered by X11, which most bars use.
+    pub fn from_number(number: u8) -> Option<Self> {
+        match number {
+            1 => Some(MouseButton::Left),
+            2 => Some(MouseButton::Middle),
+            3 => Some(MouseButton::Right),
+            4 => Some(MouseButton::ScrollUp),
+            5 => Some(MouseButton::ScrollDown),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            MouseButton::Left => "left",
+            MouseButton::Middle => "middle",
+            MouseButton::Right => "right",
+            MouseButton::ScrollUp => "scroll-up",
+            MouseButton::ScrollDown => "scroll-down",
+        }
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -28,10 +148,77 @@ pub enum MetaFormat {
     AlbumArtist,
     Title,
     Track,
+    Genre,
+    Disc,
+    /// The full release date, as given by the player
+    Date,
+    Year,
+    Composer,
+    Lyricist,
+    Comment,
+    Rating,
+    Length,
+    Url,
+    TrackId,
+    /// Any key of the player's metadata, e.g. `[xesam:audioBPM]`
+    Key(String),
+    /// A tag with filters applied to it, e.g. `[title|upper]`
+    Filtered(Box<MetaFormat>, Vec<Filter>),
+    /// A tag with its own text for when it's missing, e.g. `[artist?Unknown]`
+    Default(Box<MetaFormat>, String),
     Optional(Vec<MetaFormat>),
     String(String),
 }
 
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Filter {
+    Upper,
+    Lower,
+    TitleCase,
+    /// Cuts the text to a number of characters
+    Max(usize),
+    /// Ends text cut by `max` with an ellipsis
+    Ellipsis,
+    /// Removes every match of a pattern, where `*` matches anything
+    Strip(Pattern),
+    /// `(pattern, replacement)`
+    Replace(Pattern, String),
+}
+
+impl MetaFormat {
+    /// The tag block a name stands for.
+    pub fn from_tag(name: &str) -> Option<Self> {
+        let tag = match name {
+            "artists" => MetaFormat::Artists,
+            "artist" => MetaFormat::Artist,
+            "album" => MetaFormat::Album,
+            "album_artist" => MetaFormat::AlbumArtist,
+            "title" => MetaFormat::Title,
+            "track" => MetaFormat::Track,
+            "genre" => MetaFormat::Genre,
+            "disc" => MetaFormat::Disc,
+            "date" => MetaFormat::Date,
+            "year" => MetaFormat::Year,
+            "composer" => MetaFormat::Composer,
+            "lyricist" => MetaFormat::Lyricist,
+            "comment" => MetaFormat::Comment,
+            "rating" => MetaFormat::Rating,
+            "length" => MetaFormat::Length,
+            "url" => MetaFormat::Url,
+            "trackid" => MetaFormat::TrackId,
+            key if key.starts_with("xesam:") || key.starts_with("mpris:") => {
+                let valid_key = key.split_once(':').is_some_and(|(_, k)| !k.is_empty() && !k.contains(char::is_whitespace));
+                if !valid_key { return None; }
+
+                MetaFormat::Key(key.to_string())
+            },
+            _ => return None,
+        };
+
+        Some(tag)
+    }
+}
+
 #[derive(Debug)]
 pub enum DisplayFormatError {
     Unexpected(usize, char),
@@ -39,6 +226,12 @@ pub enum DisplayFormatError {
     WrongArgumentType(usize),
     InvalidArgument(usize),
     UnknownBlock(usize, String),
+    InvalidBinding(usize),
+    UnknownButton(usize, String),
+    UnknownCommand(usize, String),
+    InvalidCommand(usize, String),
+    UnknownCondition(usize, String),
+    UnclosedCondition,
 }
 
 impl fmt::Display for DisplayFormatError {
@@ -50,6 +243,12 @@ impl fmt::Display for DisplayFormatError {
             WrongArgumentType(pos) => write!(f, "at {}: wrong argument argument", pos),
             InvalidArgument(pos) => write!(f, "at {}: invalid argument", pos),
             UnknownBlock(pos, name) => write!(f, "at {}: unknown block '{}'", pos, name),
+            InvalidBinding(pos) => write!(f, "at {}: expected a binding like 'button=command'", pos),
+            UnknownButton(pos, name) => write!(f, "at {}: unknown mouse button '{}'", pos, name),
+            UnknownCommand(pos, name) => write!(f, "at {}: unknown command '{}'", pos, name),
+            InvalidCommand(pos, reason) => write!(f, "at {}: {}", pos, reason),
+            UnknownCondition(pos, condition) => write!(f, "at {}: unknown condition '{}'", pos, condition),
+            UnclosedCondition => write!(f, "unclosed conditional section"),
         }
     }
 }
@@ -60,7 +259,10 @@ pub fn process_display_format(format: &str) -> Result<Vec<DisplayFormat>, Displa
     const BLOCKS: &[&'static str] = &[
         "prev", "next", "play-pause",
         "info", "metadata", "time",
-        "status",
+        "status", "volume", "progress",
+        "shuffle", "loop", "seek-back", "seek-fwd",
+        "art", "lyrics", "players",
+        "stop", "raise", "quit",
     ];
 
     #[derive(PartialEq, Eq)]
@@ -69,13 +271,26 @@ pub fn process_display_format(format: &str) -> Result<Vec<DisplayFormat>, Displa
         Text,
         Block,
         ArgumentList,
+        Bindings,
+        Condition,
     }
 
     enum Value {
         Number(u8),
         Bool(bool),
+        Text(String),
     }
 
+    impl Value {
+        /// Any value can be used as text, e.g. `1` as an icon.
+        fn to_text(&self) -> String {
+            match self {
+                Value::Number(n) => n.to_string(),
+                Value::Bool(b) => b.to_string(),
+                Value::Text(s) => s.clone(),
+            }
+        }
+    }
 
     let mut state = State::Text;
     let mut buffer = String::new();
@@ -83,6 +298,12 @@ pub fn process_display_format(format: &str) -> Result<Vec<DisplayFormat>, Displa
     let mut current_block = String::new();
     let mut result = Vec::<DisplayFormat>::new();
     let mut args = Vec::<Option<Value>>::new();
+    let mut bindings = Vec::<(MouseButton, String)>::new();
+    let mut block_name = None::<String>;
+    // Conditions of the open sections and what came before them
+    let mut sections = Vec::<(Condition, Vec<DisplayFormat>)>::new();
+    // Braces of polybar and lemonbar tags, like `%{F#f00}`
+    let mut tag_braces = 0usize;
 
     macro_rules! check_arg_count {
         ($pos:expr, $name:ident, $args:ident, $len:expr) => {
@@ -108,9 +329,32 @@ pub fn process_display_format(format: &str) -> Result<Vec<DisplayFormat>, Displa
                 }
             }
         };
+
+        ($args:ident, $ind:expr, $type:ident) => {
+            if let Some(Some(v)) = $args.get($ind) {
+                if let Value::$type(_) = v { } else {
+                    return Err(WrongArgumentType($ind));
+                }
+            }
+        };
     }
 
     macro_rules! extract_arg {
+        // Icons default to the theme's, which is only known when rendering
+        (Icon, $ind:expr) => {
+            match args.get($ind) {
+                Some(Some(v)) => Some(v.to_text()),
+                _ => None,
+            }
+        };
+
+        (Text, $ind:expr, $default:expr) => {
+            match args.get($ind) {
+                Some(Some(v)) => v.to_text(),
+                _ => $default.to_string(),
+            }
+        };
+
         ($type:ident, $ind:expr, $default:expr) => {
             match args.get($ind) {
                 Some(v1) => {
@@ -131,6 +375,17 @@ pub fn process_display_format(format: &str) -> Result<Vec<DisplayFormat>, Displa
 
     macro_rules! test_block_name {
         () => {
+            // Only metadata blocks can be named, each having its own format
+            if let Some((block, name)) = current_block.clone().split_once('#') {
+                let valid_name = name.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '-');
+                if block != "metadata" || name.is_empty() || !valid_name {
+                    return Err(UnknownBlock(context_pos, current_block.clone()));
+                }
+
+                current_block = block.to_string();
+                block_name = Some(name.to_string());
+            }
+
             if !BLOCKS.contains(&current_block.as_str()) {
                 return Err(UnknownBlock(context_pos, current_block.clone()));
             }
@@ -146,19 +401,173 @@ pub fn process_display_format(format: &str) -> Result<Vec<DisplayFormat>, Displa
         };
     }
 
+    macro_rules! push_block {
+        () => {
+            let block = match current_block.as_str() {
+                "prev" => DisplayFormat::Prev(extract_arg!(Icon, 0)),
+                "stop" => DisplayFormat::Stop(extract_arg!(Icon, 0)),
+                "raise" => DisplayFormat::Raise(extract_arg!(Icon, 0)),
+                "quit" => DisplayFormat::Quit(extract_arg!(Icon, 0)),
+                "next" => DisplayFormat::Next(extract_arg!(Icon, 0)),
+                "play-pause" => DisplayFormat::PlayPause(extract_arg!(Icon, 0), extract_arg!(Icon, 1)),
+                "status" => DisplayFormat::Status(
+                    extract_arg!(Icon, 0),
+                    extract_arg!(Icon, 1),
+                    extract_arg!(Icon, 2),
+                ),
+
+                "info" => DisplayFormat::PlayerInfo(
+                    extract_arg!(Bool, 0, DEFAULT_INFO_SETTINGS.0),
+                    extract_arg!(Bool, 1, DEFAULT_INFO_SETTINGS.1),
+                ),
+
+                "metadata" => DisplayFormat::Metadata(
+                    extract_arg!(Number, 0, DEFAULT_META_SETTINGS.0),
+                    extract_arg!(Number, 1, DEFAULT_META_SETTINGS.1),
+                    block_name.take(),
+                ),
+
+                "time" => DisplayFormat::Time(
+                    extract_arg!(Bool, 0, DEFAULT_TIME_SETTINGS.0),
+                    extract_arg!(Bool, 1, DEFAULT_TIME_SETTINGS.1),
+                ),
+
+                "volume" => DisplayFormat::Volume(
+                    extract_arg!(Bool, 0, DEFAULT_VOLUME_SETTINGS.0),
+                    extract_arg!(Bool, 1, DEFAULT_VOLUME_SETTINGS.1),
+                ),
+
+                "progress" => DisplayFormat::Progress(
+                    extract_arg!(Number, 0, DEFAULT_PROGRESS_SETTINGS.0),
+                    extract_arg!(Text, 1, DEFAULT_PROGRESS_SETTINGS.1),
+                ),
+
+                "shuffle" => DisplayFormat::Shuffle(extract_arg!(Icon, 0), extract_arg!(Icon, 1)),
+
+                "loop" => DisplayFormat::Loop(
+                    extract_arg!(Icon, 0),
+                    extract_arg!(Icon, 1),
+                    extract_arg!(Icon, 2),
+                ),
+
+                "seek-back" => DisplayFormat::SeekBack(
+                    extract_arg!(Number, 0, DEFAULT_SEEK_SETTINGS.0),
+                    extract_arg!(Icon, 1),
+                ),
+
+                "seek-fwd" => DisplayFormat::SeekForward(
+                    extract_arg!(Number, 0, DEFAULT_SEEK_SETTINGS.1),
+                    extract_arg!(Icon, 1),
+                ),
+
+                "art" => DisplayFormat::Art(extract_arg!(Number, 0, DEFAULT_ART_SETTINGS)),
+
+                "players" => DisplayFormat::Players(
+                    extract_arg!(Number, 0, DEFAULT_PLAYERS_SETTINGS.0),
+                    extract_arg!(Bool, 1, DEFAULT_PLAYERS_SETTINGS.1),
+                ),
+
+                "lyrics" => DisplayFormat::Lyrics(
+                    extract_arg!(Number, 0, DEFAULT_LYRICS_SETTINGS.0),
+                    extract_arg!(Number, 1, DEFAULT_LYRICS_SETTINGS.1),
+                ),
+
+                _ => unreachable!(),
+            };
+
+            if bindings.is_empty() {
+                result.push(block);
+            } else {
+                result.push(DisplayFormat::Clickable(Box::new(block), bindings.clone()));
+                bindings.clear();
+            }
+
+            args.clear();
+        };
+    }
+
+    fn parse_binding(pos: usize, binding: &str) -> Result<(MouseButton, String), DisplayFormatError> {
+        let (button, command) = binding.split_once('=').ok_or(InvalidBinding(pos))?;
+        let (button, command) = (button.trim(), command.trim());
+
+        let button = MouseButton::from_name(button)
+            .ok_or_else(|| UnknownButton(pos, button.to_string()))?;
+
+        let (name, argument) = match command.split_once(' ') {
+            Some((name, argument)) => (name, Some(argument.trim())),
+            None => (command, None),
+        };
+
+        if name.is_empty() {
+            return Err(InvalidBinding(pos));
+        } else if !COMMANDS.contains(&name) {
+            return Err(UnknownCommand(pos, name.to_string()));
+        }
+
+        check_command_argument(name, argument).map_err(|reason| InvalidCommand(pos, reason))?;
+
+        Ok((button, command.to_string()))
+    }
+
+    fn parse_condition(pos: usize, condition: &str) -> Result<Condition, DisplayFormatError> {
+        let condition = condition.trim();
+
+        match condition {
+            "playing" => return Ok(Condition::Playing),
+            "paused" => return Ok(Condition::Paused),
+            "stopped" => return Ok(Condition::Stopped),
+            _ => (),
+        }
+
+        if let Some(tag) = condition.strip_prefix("has:") {
+            // Metadata keys have colons of their own, so only named tags work
+            if tag.trim() == "art" || MetaFormat::from_tag(tag.trim()).is_some() {
+                return Ok(Condition::Has(tag.trim().to_string()));
+            }
+        } else if let Some(comparison) = condition.strip_prefix("players") {
+            let comparison = comparison.trim_start();
+            let ordering = match comparison.chars().next() {
+                Some('>') => Some(Ordering::Greater),
+                Some('<') => Some(Ordering::Less),
+                Some('=') => Some(Ordering::Equal),
+                _ => None,
+            };
+
+            if let Some(ordering) = ordering {
+                if let Ok(count) = comparison[1..].trim().parse::<usize>() {
+                    return Ok(Condition::Players(ordering, count));
+                }
+            }
+        }
+
+        Err(UnknownCondition(pos, condition.to_string()))
+    }
+
     fn parse_value(pos: usize, value: &str) -> Result<Value, DisplayFormatError> {
         if let Ok(n) = value.parse::<u8>() { return Ok(Value::Number(n)) }
         else if let Ok(b) = value.parse::<bool>() { return Ok(Value::Bool(b)) }
 
-        // Basically strings and big numbers
-        Err(DisplayFormatError::InvalidArgument(pos))
+        // Big numbers
+        if value.parse::<i64>().is_ok() {
+            return Err(DisplayFormatError::InvalidArgument(pos));
+        }
+
+        Ok(Value::Text(value.to_string()))
     }
 
 
     fn validate_arguments(pos: usize, name: &str, args: &Vec<Option<Value>>) -> Result<(), DisplayFormatError> {
         match name {
-            "prev" | "next" | "play-pause" | "status" => {
-                check_arg_count!(pos, name, args, 0);
+            "prev" | "next" | "stop" | "raise" | "quit" => {
+                check_arg_count!(pos, name, args, 1, g);
+            }
+
+            "play-pause" => {
+                check_arg_count!(pos, name, args, 2, g);
+            }
+
+            "status" => {
+                check_arg_count!(pos, name, args, 3, g);
             }
 
             "info" => {
@@ -166,16 +575,54 @@ pub fn process_display_format(format: &str) -> Result<Vec<DisplayFormat>, Displa
                 check_arg_type!(args, Bool);
             }
 
-            "metadata" => {
+            "metadata" | "lyrics" => {
                 check_arg_count!(pos, name, args, 2, g);
                 check_arg_type!(args, Number);
             }
 
-            "time" => {
+            "time" | "volume" => {
                 check_arg_count!(pos, name, args, 2, g);
                 check_arg_type!(args, Bool);
             }
 
+            "progress" => {
+                check_arg_count!(pos, name, args, 2, g);
+                check_arg_type!(args, 0, Number);
+
+                // Either smooth or the glyphs for filled, head (optional) and empty cells
+                if let Some(Some(style)) = args.get(1) {
+                    let style = style.to_text();
+                    let glyphs = style.chars().count();
+                    if style != "smooth" && !(2..=3).contains(&glyphs) {
+                        return Err(InvalidArgument(pos));
+                    }
+                }
+            }
+
+            "shuffle" => {
+                check_arg_count!(pos, name, args, 2, g);
+            }
+
+            "loop" => {
+                check_arg_count!(pos, name, args, 3, g);
+            }
+
+            "players" => {
+                check_arg_count!(pos, name, args, 2, g);
+                check_arg_type!(args, 0, Number);
+                check_arg_type!(args, 1, Bool);
+            }
+
+            "seek-back" | "seek-fwd" => {
+                check_arg_count!(pos, name, args, 2, g);
+                check_arg_type!(args, 0, Number);
+            }
+
+            "art" => {
+                check_arg_count!(pos, name, args, 1, g);
+                check_arg_type!(args, Number);
+            }
+
             _ => (),
         }
 
@@ -230,6 +677,55 @@ pub fn process_display_format(format: &str) -> Result<Vec<DisplayFormat>, Displa
                     context_pos = pos+1;
                     state = State::ArgumentList;
                     buffer.clear();
+
+                    // Start the section, `has:tag` has a colon of its own
+                } else if state == State::Condition && buffer.trim() != "has" {
+                    let condition = parse_condition(context_pos, &buffer)?;
+                    sections.push((condition, mem::take(&mut result)));
+                    buffer.clear();
+                    state = State::Text;
+                } else {
+                    buffer.push(c);
+                }
+            }
+
+            // Open conditional section
+            '{' => {
+                if state == State::Escape {
+                    escape_char!();
+                } else if state == State::Text && buffer.ends_with('%') {
+                    buffer.push(c);
+                    tag_braces += 1;
+                } else if state == State::Text {
+                    flush_buffer!();
+                    state = State::Condition;
+                    context_pos = pos+1;
+                } else if state == State::Condition {
+                    unexpected!();
+                } else {
+                    buffer.push(c);
+                }
+            }
+
+            // Close conditional section
+            '}' => {
+                if state == State::Escape {
+                    escape_char!();
+                } else if state == State::Text && tag_braces > 0 {
+                    buffer.push(c);
+                    tag_braces -= 1;
+                } else if state == State::Text {
+                    flush_buffer!();
+
+                    match sections.pop() {
+                        Some((condition, before)) => {
+                            let section = mem::replace(&mut result, before);
+                            result.push(DisplayFormat::Conditional(condition, section));
+                        },
+                        None => { unexpected!(); },
+                    }
+                } else if state == State::Condition {
+                    unexpected!();
                 } else {
                     buffer.push(c);
                 }
@@ -251,6 +747,32 @@ pub fn process_display_format(format: &str) -> Result<Vec<DisplayFormat>, Displa
                 }
             }
 
+            // Start block bindings
+            ';' => {
+                if state == State::Block {
+                    current_block = buffer.trim().to_string();
+                    test_block_name!();
+                    buffer.clear();
+                    validate_arguments(context_pos, &current_block, &args)?;
+                    context_pos = pos+1;
+                    state = State::Bindings;
+                } else if state == State::ArgumentList {
+                    if !buffer.is_empty() {
+                        args.push(Some(parse_value(context_pos, buffer.trim())?));
+                    }
+                    buffer.clear();
+                    validate_arguments(context_pos, &current_block, &args)?;
+                    context_pos = pos+1;
+                    state = State::Bindings;
+                } else if state == State::Bindings {
+                    bindings.push(parse_binding(context_pos, &buffer)?);
+                    buffer.clear();
+                    context_pos = pos+1;
+                } else {
+                    buffer.push(c);
+                }
+            }
+
             // Close block
             ']' => {
                 // Blocks without arguments
@@ -261,28 +783,7 @@ pub fn process_display_format(format: &str) -> Result<Vec<DisplayFormat>, Displa
                     test_block_name!();
                     buffer.clear();
                     validate_arguments(context_pos, &current_block, &args)?;
-
-                    result.push(match current_block.as_str() {
-                        "prev" => DisplayFormat::Prev,
-                        "next" => DisplayFormat::Next,
-                        "play-pause" => DisplayFormat::PlayPause,
-                        "status" => DisplayFormat::Status,
-                        "info" => DisplayFormat::PlayerInfo(
-                            DEFAULT_INFO_SETTINGS.0, DEFAULT_INFO_SETTINGS.1,
-                        ),
-
-                        "metadata" => DisplayFormat::Metadata(
-                            DEFAULT_META_SETTINGS.0, DEFAULT_META_SETTINGS.1,
-                        ),
-
-                        "time" => DisplayFormat::Time(
-                            DEFAULT_TIME_SETTINGS.0, DEFAULT_TIME_SETTINGS.1,
-                        ),
-
-                        _ => unreachable!(),
-                    });
-
-
+                    push_block!();
                     state = State::Text;
 
                     // Blocks with arguments
@@ -292,28 +793,14 @@ pub fn process_display_format(format: &str) -> Result<Vec<DisplayFormat>, Displa
                     }
                     buffer.clear();
                     validate_arguments(context_pos, &current_block, &args)?;
+                    push_block!();
+                    state = State::Text;
 
-                    result.push(match current_block.as_str() {
-                        "info" => DisplayFormat::PlayerInfo(
-                            extract_arg!(Bool, 0, DEFAULT_INFO_SETTINGS.0),
-                            extract_arg!(Bool, 1, DEFAULT_INFO_SETTINGS.1),
-                        ),
-
-                        "metadata" => DisplayFormat::Metadata(
-                            extract_arg!(Number, 0, DEFAULT_META_SETTINGS.0),
-                            extract_arg!(Number, 1, DEFAULT_META_SETTINGS.1),
-                        ),
-
-                        "time" => DisplayFormat::Time(
-                            extract_arg!(Bool, 0, DEFAULT_TIME_SETTINGS.0),
-                            extract_arg!(Bool, 1, DEFAULT_TIME_SETTINGS.1),
-                        ),
-
-                        _ => unreachable!(),
-                    });
-
-                    args.clear();
-
+                    // Blocks with bindings
+                } else if state == State::Bindings {
+                    bindings.push(parse_binding(context_pos, &buffer)?);
+                    buffer.clear();
+                    push_block!();
                     state = State::Text;
                 } else {
                     unexpected!();
@@ -324,6 +811,10 @@ pub fn process_display_format(format: &str) -> Result<Vec<DisplayFormat>, Displa
         }
     }
 
+    if state == State::Condition || !sections.is_empty() {
+        return Err(UnclosedCondition);
+    }
+
     flush_buffer!();
     Ok(result)
 }
@@ -332,7 +823,11 @@ pub fn process_display_format(format: &str) -> Result<Vec<DisplayFormat>, Displa
 pub enum MetaFormatError {
     Unexpected(usize, char),
     UnknownBlock(usize, String),
+    UnknownFilter(usize, String),
+    InvalidFilter(usize, String),
+    InvalidRegex(usize, String),
     UnclosedOptional,
+    UnclosedQuote,
 }
 
 impl fmt::Display for MetaFormatError {
@@ -341,24 +836,71 @@ impl fmt::Display for MetaFormatError {
         match self {
             Unexpected(pos, m) => write!(f, "at {}: unexpected '{}'", pos, m),
             UnknownBlock(pos, name) => write!(f, "at {}: unknown block '{}'", pos, name),
+            UnknownFilter(pos, name) => write!(f, "at {}: unknown filter '{}'", pos, name),
+            InvalidFilter(pos, name) => write!(f, "at {}: invalid arguments for filter '{}'", pos, name),
+            InvalidRegex(pos, e) => write!(f, "at {}: invalid pattern, {}", pos, e),
             UnclosedOptional => write!(f, ": reached end-of-line with an unclosed optional tag"),
+            UnclosedQuote => write!(f, ": reached end-of-line with an unclosed quote"),
         }
     }
 }
 
+/// Runs the text of a tag through its filters, in order.
+pub fn apply_filters(text: &str, filters: &[Filter]) -> String {
+    let mut text = text.to_string();
+    let mut cut = false;
+
+    for filter in filters {
+        text = match filter {
+            Filter::Upper => text.to_uppercase(),
+            Filter::Lower => text.to_lowercase(),
+            Filter::TitleCase => {
+                let mut previous = ' ';
+
+                text.chars().flat_map(|c| {
+                    // Not after apostrophes, as in "Don't"
+                    let word_start = !previous.is_alphanumeric() && previous != '\'';
+                    previous = c;
+
+                    if word_start {
+                        c.to_uppercase().collect::<Vec<_>>()
+                    } else {
+                        c.to_lowercase().collect()
+                    }
+                }).collect()
+            },
+            Filter::Max(size) => if text.chars().count() > *size {
+                cut = true;
+                text.chars().take(*size).collect()
+            } else {
+                text
+            },
+            // Replaces the last character, so it still fits
+            Filter::Ellipsis => if cut && !text.is_empty() {
+                text.pop();
+                format!("{}…", text.trim_end())
+            } else {
+                text
+            },
+            Filter::Strip(pattern) => pattern.replace_all(&text, "").trim().to_string(),
+            Filter::Replace(pattern, replacement) => pattern.replace_all(&text, replacement),
+        };
+    }
+
+    text
+}
+
 pub fn process_meta_format(format: &str) -> Result<Vec<MetaFormat>, MetaFormatError> {
     use MetaFormatError::*;
 
-    const BLOCKS: &[&'static str] = &[
-        "artists", "artist", "album_artist",
-        "album", "title", "track",
-    ];
-
     #[derive(PartialEq, Eq)]
     enum State {
         Escape,
         Text,
         Block,
+        /// In a filter argument, like `[title|strip:"[*]"]`
+        Quoted,
+        QuotedEscape,
     }
 
     let mut state_stack  = vec![State::Text];
@@ -367,12 +909,6 @@ pub fn process_meta_format(format: &str) -> Result<Vec<MetaFormat>, MetaFormatEr
     let mut buffer = String::new();
     let mut context_pos = 0;
 
-    macro_rules! test_block_name {
-        ($name:ident) => {
-            if !BLOCKS.contains(&$name.as_str()) { return Err(UnknownBlock(context_pos, $name.clone())); }
-        };
-    }
-
     macro_rules! flush_buffer {
         () => {
             if buffer.len() > 0 {
@@ -382,6 +918,94 @@ pub fn process_meta_format(format: &str) -> Result<Vec<MetaFormat>, MetaFormatEr
         };
     }
 
+    /// Splits at a separator, except between quotes.
+    fn split_unquoted(text: &str, separator: char) -> Vec<&str> {
+        let mut parts = Vec::new();
+        let mut start = 0;
+        let mut quoted = false;
+        let mut escaped = false;
+
+        for (i, c) in text.char_indices() {
+            if escaped {
+                escaped = false;
+            } else if quoted && c == '\\' {
+                escaped = true;
+            } else if c == '"' {
+                quoted = !quoted;
+            } else if c == separator && !quoted {
+                parts.push(&text[start..i]);
+                start = i + c.len_utf8();
+            }
+        }
+
+        parts.push(&text[start..]);
+        parts
+    }
+
+    /// A filter argument without its quotes. Other escapes are kept for
+    /// patterns, as in `replace:"\d+"`.
+    fn unquote(arg: &str) -> String {
+        let arg = arg.trim();
+
+        match arg.strip_prefix('"').and_then(|a| a.strip_suffix('"')) {
+            Some(arg) => arg.replace("\\\"", "\""),
+            None => arg.to_string(),
+        }
+    }
+
+    fn parse_filter(pos: usize, filter: &str) -> Result<Filter, MetaFormatError> {
+        let (name, args) = match filter.split_once(':') {
+            Some((name, args)) => (name.trim(), split_unquoted(args, ',').into_iter().map(unquote).collect()),
+            None => (filter.trim(), Vec::new()),
+        };
+
+        let filter = match (name, args.as_slice()) {
+            ("upper", []) => Filter::Upper,
+            ("lower", []) => Filter::Lower,
+            ("title-case", []) => Filter::TitleCase,
+            ("ellipsis", []) => Filter::Ellipsis,
+            ("max", [size]) => match size.parse() {
+                Ok(size) => Filter::Max(size),
+                Err(_) => return Err(InvalidFilter(pos, name.to_string())),
+            },
+            ("strip", [pattern]) => Filter::Strip(Pattern::from_glob(pattern)),
+            ("replace", [pattern]) | ("replace", [pattern, _]) => match Pattern::new(pattern) {
+                Ok(pattern) => Filter::Replace(pattern, args.get(1).cloned().unwrap_or_default()),
+                Err(e) => return Err(InvalidRegex(pos, e.to_string())),
+            },
+            ("upper" | "lower" | "title-case" | "ellipsis" | "max" | "strip" | "replace", _) => {
+                return Err(InvalidFilter(pos, name.to_string()));
+            },
+            _ => return Err(UnknownFilter(pos, name.to_string())),
+        };
+
+        Ok(filter)
+    }
+
+    fn parse_tag(pos: usize, block: &str) -> Result<MetaFormat, MetaFormatError> {
+        // The default comes last, after the filters
+        let (block, default) = match split_unquoted(block, '?').split_first() {
+            Some((block, [])) => (*block, None),
+            Some((block, default)) => (*block, Some(unquote(&default.join("?")))),
+            None => (block, None),
+        };
+
+        let mut parts = split_unquoted(block, '|').into_iter();
+        let name = parts.next().unwrap_or_default().trim();
+
+        let mut tag = MetaFormat::from_tag(name).ok_or_else(|| UnknownBlock(pos, name.to_string()))?;
+        let filters = parts.map(|filter| parse_filter(pos, filter)).collect::<Result<Vec<_>, _>>()?;
+
+        if !filters.is_empty() {
+            tag = MetaFormat::Filtered(Box::new(tag), filters);
+        }
+
+        match default {
+            Some(default) => Ok(MetaFormat::Default(Box::new(tag), default)),
+            None => Ok(tag),
+        }
+    }
+
     for (pos, c) in format.chars().enumerate() {
         macro_rules! escape_char {
             () => {
@@ -396,6 +1020,31 @@ pub fn process_meta_format(format: &str) -> Result<Vec<MetaFormat>, MetaFormatEr
             };
         }
 
+        // Filter arguments can have any character between quotes
+        match state_stack[stack_index] {
+            State::Quoted => {
+                buffer.push(c);
+
+                match c {
+                    '\\' => state_stack[stack_index] = State::QuotedEscape,
+                    '"' => state_stack[stack_index] = State::Block,
+                    _ => (),
+                }
+                continue;
+            },
+            State::QuotedEscape => {
+                buffer.push(c);
+                state_stack[stack_index] = State::Quoted;
+                continue;
+            },
+            State::Block if c == '"' => {
+                buffer.push(c);
+                state_stack[stack_index] = State::Quoted;
+                continue;
+            },
+            _ => (),
+        }
+
         match c {
             // Escape special character
             '\\' => {
@@ -427,17 +1076,7 @@ pub fn process_meta_format(format: &str) -> Result<Vec<MetaFormat>, MetaFormatEr
                 if state_stack[stack_index] == State::Block {
                     let name = buffer.trim().to_string();
                     buffer.clear();
-                    test_block_name!(name);
-
-                    block_stack[stack_index].push(match name.as_str() {
-                        "artists" => MetaFormat::Artists,
-                        "artist" => MetaFormat::Artist,
-                        "album" => MetaFormat::Album,
-                        "album_artist" => MetaFormat::AlbumArtist,
-                        "title" => MetaFormat::Title,
-                        "track" => MetaFormat::Track,
-                        _ => unreachable!(),
-                    });
+                    block_stack[stack_index].push(parse_tag(context_pos, &name)?);
 
                     context_pos = pos+1;
                     state_stack[stack_index] = State::Text;
@@ -485,7 +1124,9 @@ pub fn process_meta_format(format: &str) -> Result<Vec<MetaFormat>, MetaFormatEr
         }
     }
 
-    if stack_index > 0 {
+    if matches!(state_stack[stack_index], State::Quoted | State::QuotedEscape) {
+        return Err(UnclosedQuote);
+    } else if stack_index > 0 {
         return Err(UnclosedOptional);
     }
 
@@ -499,17 +1140,19 @@ fn test_display_format() {
     use DisplayFormat::*;
     use super::DEFAULT_DISPLAY_FORMAT;
 
-    assert_eq!(process_display_format(DEFAULT_DISPLAY_FORMAT).unwrap(), [
-        Prev, String(" ".to_string()), PlayPause, String(" ".to_string()),
-        Next, String(" ".to_string()), PlayerInfo(true, true),
-        String(" ┃ ".to_string()), Metadata(32, 10),
+    // The default differs between debug and release builds
+    assert!(process_display_format(DEFAULT_DISPLAY_FORMAT).is_ok());
+    assert_eq!(process_display_format("[prev] [play-pause] [next] [info] ┃ [metadata]").unwrap(), [
+        Prev(None), String(" ".to_string()), PlayPause(None, None), String(" ".to_string()),
+        Next(None), String(" ".to_string()), PlayerInfo(true, true),
+        String(" ┃ ".to_string()), Metadata(32, 10, None),
     ]);
 
     assert_eq!(process_display_format("[[]").is_err(), true);
-    assert_eq!(process_display_format("[prev]").unwrap(), [Prev]);
-    assert_eq!(process_display_format("[metadata:]").unwrap(), [Metadata(32, 10)]);
-    assert_eq!(process_display_format("[metadata:,]").unwrap(), [Metadata(32, 10)]);
-    assert_eq!(process_display_format("[metadata:,11]").unwrap(), [Metadata(32, 11)]);
+    assert_eq!(process_display_format("[prev]").unwrap(), [Prev(None)]);
+    assert_eq!(process_display_format("[metadata:]").unwrap(), [Metadata(32, 10, None)]);
+    assert_eq!(process_display_format("[metadata:,]").unwrap(), [Metadata(32, 10, None)]);
+    assert_eq!(process_display_format("[metadata:,11]").unwrap(), [Metadata(32, 11, None)]);
     assert_eq!(process_display_format("[metadata:,,]").is_err(), false);
     assert_eq!(process_display_format("[metadata:,,11]").is_err(), true);
 }
@@ -522,4 +1165,172 @@ fn test_meta_format() {
     assert_eq!(process_meta_format(DEFAULT_META_FORMAT).unwrap(), [
         Optional(vec![Artist, String(" - ".to_string())]), Title,
     ]);
+
+    assert_eq!(process_meta_format("[composer]: [title] <([year])> [xesam:audioBPM]").unwrap(), [
+        Composer, String(": ".to_string()), Title, String(" ".to_string()),
+        Optional(vec![String("(".to_string()), Year, String(")".to_string())]),
+        String(" ".to_string()), Key("xesam:audioBPM".to_string()),
+    ]);
+    assert!(process_meta_format("[xesam:]").is_err());
+    assert!(process_meta_format("[bpm]").is_err());
+}
+
+#[test]
+fn test_meta_filters() {
+    use MetaFormat::*;
+
+    assert_eq!(process_meta_format(r#"[title|strip:" [*]"|max:20|ellipsis] <\<>"#).unwrap(), [
+        Filtered(Box::new(Title), vec![
+            Filter::Strip(Pattern::from_glob(" [*]")),
+            Filter::Max(20),
+            Filter::Ellipsis,
+        ]),
+        String(" ".to_string()),
+        Optional(vec![String("<".to_string())]),
+    ]);
+
+    assert_eq!(process_meta_format(r#"[artist | replace:"\s*\"(.*)\"",", $1" | upper]"#).unwrap(), [
+        Filtered(Box::new(Artist), vec![
+            Filter::Replace(Pattern::new(r#"\s*"(.*)""#).unwrap(), ", $1".to_string()),
+            Filter::Upper,
+        ]),
+    ]);
+
+    assert_eq!(process_meta_format(r#"[artist?Unknown Artist] [album?][title|upper?"Why?"]"#).unwrap(), [
+        Default(Box::new(Artist), "Unknown Artist".to_string()),
+        String(" ".to_string()),
+        Default(Box::new(Album), "".to_string()),
+        Default(Box::new(Filtered(Box::new(Title), vec![Filter::Upper])), "Why?".to_string()),
+    ]);
+
+    assert!(process_meta_format("[title|loud]").is_err());
+    assert!(process_meta_format("[title|max:many]").is_err());
+    assert!(process_meta_format("[title|upper:1]").is_err());
+    assert!(process_meta_format("[title|replace:\"(\"]").is_err());
+    assert!(process_meta_format("[title|strip:\"]").is_err());
+
+    let filters = [Filter::Strip(Pattern::from_glob(" (*Remaster*)")), Filter::Max(10), Filter::Ellipsis];
+    assert_eq!(apply_filters("Song (2011 Remaster)", &filters), "Song");
+    assert_eq!(apply_filters("A Very Long Song Title", &filters), "A Very Lo…");
+    assert_eq!(apply_filters("don't STOP me now", &[Filter::TitleCase]), "Don't Stop Me Now");
+
+    match process_meta_format(r#"[title|replace:"\s*[\(\[]Official (Music )?Video[\)\]]",""]"#).unwrap().as_slice() {
+        [Filtered(_, filters)] => assert_eq!(apply_filters("Song [Official Music Video]", filters), "Song"),
+        format => panic!("unexpected format {:?}", format),
+    }
+}
+
+#[test]
+fn test_display_bindings() {
+    use DisplayFormat::*;
+
+    assert_eq!(process_display_format("[info;scroll-up=next-player;scroll-down=prev-player]").unwrap(), [
+        Clickable(Box::new(PlayerInfo(true, true)), vec![
+            (MouseButton::ScrollUp, "next-player".to_string()),
+            (MouseButton::ScrollDown, "prev-player".to_string()),
+        ]),
+    ]);
+
+    assert_eq!(process_display_format("[metadata:20;middle=next] ").unwrap(), [
+        Clickable(Box::new(Metadata(20, 10, None)), vec![(MouseButton::Middle, "next".to_string())]),
+        String(" ".to_string()),
+    ]);
+
+    assert!(process_display_format("[prev:]").is_ok());
+    assert_eq!(process_display_format("[volume:true;left=volume 50]").unwrap(), [
+        Clickable(Box::new(Volume(true, true)), vec![(MouseButton::Left, "volume 50".to_string())]),
+    ]);
+    assert!(process_display_format("[prev;middle]").is_err());
+    assert!(process_display_format("[prev;wheel=next]").is_err());
+    assert!(process_display_format("[prev;left=dance]").is_err());
+    assert!(process_display_format("[prev;left=volume 500]").is_err());
+    assert!(process_display_format("[prev;left=next 1]").is_err());
+
+    assert_eq!(process_display_format("[seek-back][seek-fwd:10;scroll-up=seek +5;right=seek-to 1:23]").unwrap(), [
+        SeekBack(15, None),
+        Clickable(Box::new(SeekForward(10, None)), vec![
+            (MouseButton::ScrollUp, "seek +5".to_string()),
+            (MouseButton::Right, "seek-to 1:23".to_string()),
+        ]),
+    ]);
+}
+
+#[test]
+fn test_display_text_arguments() {
+    use DisplayFormat::*;
+
+    assert_eq!(process_display_format("[progress]").unwrap(), [Progress(10, "smooth".to_string())]);
+    assert_eq!(process_display_format("[progress:20,=>-]").unwrap(), [Progress(20, "=>-".to_string())]);
+    assert!(process_display_format("[progress:wide]").is_err());
+    assert!(process_display_format("[progress:10,#]").is_err());
+    assert!(process_display_format("[metadata:300]").is_err());
+    assert!(process_display_format("[time:yes]").is_err());
+
+    assert_eq!(process_display_format("[shuffle:S,-]").unwrap(), [Shuffle(Some("S".to_string()), Some("-".to_string()))]);
+    assert_eq!(process_display_format("[loop:,1,all]").unwrap(), [
+        Loop(None, Some("1".to_string()), Some("all".to_string())),
+    ]);
+    assert!(process_display_format("[loop:a,b,c,d]").is_err());
+
+    assert_eq!(process_display_format("[art][art:64]").unwrap(), [Art(0), Art(64)]);
+    assert!(process_display_format("[art:big]").is_err());
+    assert_eq!(process_display_format("[lyrics][lyrics:40,5]").unwrap(), [Lyrics(32, 10), Lyrics(40, 5)]);
+    assert_eq!(process_display_format("[players:,false]").unwrap(), [Players(10, false)]);
+    assert!(process_display_format("[players:true]").is_err());
+    assert_eq!(process_display_format("[metadata#title:20] [metadata#album_name]").unwrap(), [
+        Metadata(20, 10, Some("title".to_string())),
+        String(" ".to_string()),
+        Metadata(32, 10, Some("album_name".to_string())),
+    ]);
+    assert!(process_display_format("[metadata#]").is_err());
+    assert!(process_display_format("[metadata#a b]").is_err());
+    assert!(process_display_format("[time#elapsed]").is_err());
+    assert_eq!(process_display_format("[stop][raise][quit]").unwrap(), [Stop(None), Raise(None), Quit(None)]);
+}
+
+#[test]
+fn test_display_icon_overrides() {
+    use DisplayFormat::*;
+
+    assert_eq!(process_display_format("[prev:<<][play-pause:>,=][status:,,stop]").unwrap(), [
+        Prev(Some("<<".to_string())),
+        PlayPause(Some(">".to_string()), Some("=".to_string())),
+        Status(None, None, Some("stop".to_string())),
+    ]);
+    assert_eq!(process_display_format("[seek-back:5,<][seek-fwd:,>]").unwrap(), [
+        SeekBack(5, Some("<".to_string())),
+        SeekForward(30, Some(">".to_string())),
+    ]);
+    assert!(process_display_format("[next:a,b]").is_err());
+    assert!(process_display_format("[seek-back:<]").is_err());
+}
+
+#[test]
+fn test_display_conditions() {
+    use DisplayFormat::*;
+
+    assert_eq!(process_display_format("{playing:[time] }{paused:(paused) }[metadata]").unwrap(), [
+        Conditional(Condition::Playing, vec![Time(true, false), String(" ".to_string())]),
+        Conditional(Condition::Paused, vec![String("(paused) ".to_string())]),
+        Metadata(32, 10, None),
+    ]);
+
+    assert_eq!(process_display_format("{players>1:[info] {has:album:on}}").unwrap(), [
+        Conditional(Condition::Players(Ordering::Greater, 1), vec![
+            PlayerInfo(true, true),
+            String(" ".to_string()),
+            Conditional(Condition::Has("album".to_string()), vec![String("on".to_string())]),
+        ]),
+    ]);
+
+    assert_eq!(process_display_format("%{F#f00}[prev]%{F-} \\{x\\}").unwrap(), [
+        String("%{F#f00}".to_string()), Prev(None), String("%{F-} {x}".to_string()),
+    ]);
+
+    assert!(process_display_format("{loud:[time]}").is_err());
+    assert!(process_display_format("{has:lyrics:[time]}").is_err());
+    assert!(process_display_format("{players>many:[info]}").is_err());
+    assert!(process_display_format("{players:[info]}").is_err());
+    assert!(process_display_format("{playing:[time]").is_err());
+    assert!(process_display_format("[time]}").is_err());
 }
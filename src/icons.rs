@@ -0,0 +1,227 @@
+/// The icons used by the blocks, picked with `--icons` and overridable one by
+/// one with `--icon name=icon`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IconTheme {
+    pub play: String,
+    pub pause: String,
+    pub stopped: String,
+    pub prev: String,
+    pub next: String,
+    pub raise: String,
+    pub quit: String,
+    pub volume_off: String,
+    pub volume_low: String,
+    pub volume_high: String,
+    pub shuffle_on: String,
+    pub shuffle_off: String,
+    pub loop_none: String,
+    pub loop_track: String,
+    pub loop_playlist: String,
+    pub seek_back: String,
+    pub seek_forward: String,
+    /// Shown before the empty message when it isn't set.
+    pub empty: String,
+}
+
+pub const THEMES: &[&'static str] = &["fontawesome", "nerd", "material", "ascii", "emoji"];
+
+/// The names of the icons, as given to `--icon`.
+pub const ICONS: &[&'static str] = &[
+    "play", "pause", "stopped", "prev", "next", "raise", "quit",
+    "volume-off", "volume-low", "volume-high", "shuffle-on", "shuffle-off",
+    "loop-none", "loop-track", "loop-playlist", "seek-back", "seek-fwd",
+    "empty",
+];
+
+macro_rules! theme {
+    ($($icon:ident: $value:expr),* $(,)?) => {
+        IconTheme { $($icon: $value.to_string()),* }
+    };
+}
+
+impl IconTheme {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            // Font Awesome 5, the regular style where there's one
+            "fontawesome" => Some(theme! {
+                play: "\u{f144}",
+                pause: "\u{f28b}",
+                stopped: "\u{f28d}",
+                prev: "\u{f04a}",
+                next: "\u{f04e}",
+                raise: "\u{f2d0}",
+                quit: "\u{f410}",
+                volume_off: "\u{f026}",
+                volume_low: "\u{f027}",
+                volume_high: "\u{f028}",
+                shuffle_on: "\u{f074}",
+                shuffle_off: "\u{f178}",
+                loop_none: "\u{f178}",
+                loop_track: "\u{f01e}",
+                loop_playlist: "\u{f021}",
+                seek_back: "\u{f0e2}",
+                seek_forward: "\u{f2f9}",
+                empty: "\u{f057}",
+            }),
+
+            // The Font Awesome 4 set every Nerd Font is patched with
+            "nerd" => Some(theme! {
+                play: "\u{f04b}",
+                pause: "\u{f04c}",
+                stopped: "\u{f04d}",
+                prev: "\u{f048}",
+                next: "\u{f051}",
+                raise: "\u{f2d0}",
+                quit: "\u{f00d}",
+                volume_off: "\u{f026}",
+                volume_low: "\u{f027}",
+                volume_high: "\u{f028}",
+                shuffle_on: "\u{f074}",
+                shuffle_off: "\u{f178}",
+                loop_none: "\u{f178}",
+                loop_track: "\u{f01e}",
+                loop_playlist: "\u{f021}",
+                seek_back: "\u{f04a}",
+                seek_forward: "\u{f04e}",
+                empty: "\u{f057}",
+            }),
+
+            // Material Icons codepoints
+            "material" => Some(theme! {
+                play: "\u{e037}",
+                pause: "\u{e034}",
+                stopped: "\u{e047}",
+                prev: "\u{e045}",
+                next: "\u{e044}",
+                raise: "\u{e89e}",
+                quit: "\u{e5cd}",
+                volume_off: "\u{e04f}",
+                volume_low: "\u{e04d}",
+                volume_high: "\u{e050}",
+                shuffle_on: "\u{e043}",
+                shuffle_off: "\u{e8e4}",
+                loop_none: "\u{e8e4}",
+                loop_track: "\u{e041}",
+                loop_playlist: "\u{e040}",
+                seek_back: "\u{e059}",
+                seek_forward: "\u{e056}",
+                empty: "\u{e440}",
+            }),
+
+            "ascii" => Some(theme! {
+                play: "|>",
+                pause: "||",
+                stopped: "[]",
+                prev: "|<",
+                next: ">|",
+                raise: "^",
+                quit: "x",
+                volume_off: "<x",
+                volume_low: "<)",
+                volume_high: "<))",
+                shuffle_on: "~>",
+                shuffle_off: "->",
+                loop_none: "->",
+                loop_track: "@1",
+                loop_playlist: "@",
+                seek_back: "<<",
+                seek_forward: ">>",
+                empty: "--",
+            }),
+
+            "emoji" => Some(theme! {
+                play: "\u{25b6}\u{fe0f}",
+                pause: "\u{23f8}\u{fe0f}",
+                stopped: "\u{23f9}\u{fe0f}",
+                prev: "\u{23ee}\u{fe0f}",
+                next: "\u{23ed}\u{fe0f}",
+                raise: "\u{1f53c}",
+                quit: "\u{274c}",
+                volume_off: "\u{1f507}",
+                volume_low: "\u{1f509}",
+                volume_high: "\u{1f50a}",
+                shuffle_on: "\u{1f500}",
+                shuffle_off: "\u{27a1}\u{fe0f}",
+                loop_none: "\u{27a1}\u{fe0f}",
+                loop_track: "\u{1f502}",
+                loop_playlist: "\u{1f501}",
+                seek_back: "\u{23ea}",
+                seek_forward: "\u{23e9}",
+                empty: "\u{1f507}",
+            }),
+
+            _ => None,
+        }
+    }
+
+    /// Replaces a single icon, returning false if there's no icon by that name.
+    pub fn set(&mut self, name: &str, icon: &str) -> bool {
+        match self.icon_mut(name) {
+            Some(field) => {
+                *field = icon.to_string();
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// Passes every icon through `f`, e.g. to escape them for the markup.
+    pub fn map_icons(&mut self, f: impl Fn(&str) -> String) {
+        for name in ICONS {
+            let field = self.icon_mut(name).expect("known icon");
+            *field = f(field);
+        }
+    }
+
+    fn icon_mut(&mut self, name: &str) -> Option<&mut String> {
+        let field = match name {
+            "play" => &mut self.play,
+            "pause" => &mut self.pause,
+            "stopped" => &mut self.stopped,
+            "prev" => &mut self.prev,
+            "next" => &mut self.next,
+            "raise" => &mut self.raise,
+            "quit" => &mut self.quit,
+            "volume-off" => &mut self.volume_off,
+            "volume-low" => &mut self.volume_low,
+            "volume-high" => &mut self.volume_high,
+            "shuffle-on" => &mut self.shuffle_on,
+            "shuffle-off" => &mut self.shuffle_off,
+            "loop-none" => &mut self.loop_none,
+            "loop-track" => &mut self.loop_track,
+            "loop-playlist" => &mut self.loop_playlist,
+            "seek-back" => &mut self.seek_back,
+            "seek-fwd" => &mut self.seek_forward,
+            "empty" => &mut self.empty,
+            _ => return None,
+        };
+
+        Some(field)
+    }
+}
+
+impl Default for IconTheme {
+    fn default() -> Self {
+        Self::from_name("fontawesome").expect("built-in theme")
+    }
+}
+
+#[test]
+fn test_icon_themes() {
+    for name in THEMES {
+        assert!(IconTheme::from_name(name).is_some(), "missing theme {}", name);
+    }
+
+    let mut theme = IconTheme::from_name("ascii").unwrap();
+    assert_eq!((theme.play.as_str(), theme.pause.as_str()), ("|>", "||"));
+
+    for name in ICONS {
+        assert!(theme.set(name, "?"), "can't set {}", name);
+    }
+    assert_eq!(theme.seek_forward, "?");
+    assert!(!theme.set("seek-forward", "?"));
+
+    theme.map_icons(|icon| format!("<{}>", icon));
+    assert_eq!((theme.play.as_str(), theme.empty.as_str()), ("<?>", "<?>"));
+    assert_eq!(IconTheme::from_name("wingdings"), None);
+}
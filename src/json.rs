@@ -0,0 +1,390 @@
+use std::{
+    fmt,
+    iter::Peekable,
+    str::Chars,
+};
+use mpris::MetadataValue;
+
+/// A minimal JSON value, just enough for the JSON based markup types.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    /// Keys are kept in insertion order.
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    pub fn object() -> Self {
+        Json::Object(Vec::new())
+    }
+
+    /// Appends a key to an object, builder style. Does nothing for other values.
+    pub fn with<K: Into<String>, V: Into<Json>>(mut self, key: K, value: V) -> Self {
+        if let Json::Object(ref mut pairs) = self {
+            pairs.push((key.into(), value.into()));
+        }
+        self
+    }
+}
+
+#[derive(Debug)]
+pub enum JsonError {
+    Unexpected(usize, char),
+    UnexpectedEnd,
+    InvalidNumber(usize),
+    InvalidEscape(usize),
+}
+
+impl fmt::Display for JsonError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use JsonError::*;
+        match self {
+            Unexpected(pos, c) => write!(f, "at {}: unexpected '{}'", pos, c),
+            UnexpectedEnd => write!(f, "unexpected end of input"),
+            InvalidNumber(pos) => write!(f, "at {}: invalid number", pos),
+            InvalidEscape(pos) => write!(f, "at {}: invalid escape sequence", pos),
+        }
+    }
+}
+
+impl Json {
+    pub fn parse(input: &str) -> Result<Json, JsonError> {
+        let mut parser = Parser { chars: input.chars().peekable(), pos: 0 };
+        let value = parser.value()?;
+
+        parser.skip_whitespace();
+        match parser.next() {
+            Some(c) => Err(JsonError::Unexpected(parser.pos-1, c)),
+            None => Ok(value),
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(pairs) => pairs.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Json::Number(n) => Some(*n),
+            _ => None,
+        }
+    }
+}
+
+struct Parser<'a> {
+    chars: Peekable<Chars<'a>>,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn next(&mut self) -> Option<char> {
+        let c = self.chars.next();
+        if c.is_some() { self.pos += 1; }
+        c
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(c) = self.chars.peek() {
+            if !c.is_whitespace() { break; }
+            self.next();
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), JsonError> {
+        match self.next() {
+            Some(c) if c == expected => Ok(()),
+            Some(c) => Err(JsonError::Unexpected(self.pos-1, c)),
+            None => Err(JsonError::UnexpectedEnd),
+        }
+    }
+
+    fn keyword(&mut self, word: &str, value: Json) -> Result<Json, JsonError> {
+        for expected in word.chars() {
+            self.expect(expected)?;
+        }
+        Ok(value)
+    }
+
+    fn value(&mut self) -> Result<Json, JsonError> {
+        self.skip_whitespace();
+
+        match self.chars.peek().copied() {
+            Some('n') => self.keyword("null", Json::Null),
+            Some('t') => self.keyword("true", Json::Bool(true)),
+            Some('f') => self.keyword("false", Json::Bool(false)),
+            Some('"') => Ok(Json::String(self.string()?)),
+            Some('[') => self.array(),
+            Some('{') => self.object(),
+            Some(c) if c == '-' || c.is_ascii_digit() => self.number(),
+            Some(c) => Err(JsonError::Unexpected(self.pos, c)),
+            None => Err(JsonError::UnexpectedEnd),
+        }
+    }
+
+    fn number(&mut self) -> Result<Json, JsonError> {
+        let start = self.pos;
+        let mut buffer = String::new();
+
+        while let Some(c) = self.chars.peek().copied() {
+            if c.is_ascii_digit() || "+-.eE".contains(c) {
+                buffer.push(c);
+                self.next();
+            } else {
+                break;
+            }
+        }
+
+        buffer.parse::<f64>()
+            .map(Json::Number)
+            .map_err(|_| JsonError::InvalidNumber(start))
+    }
+
+    fn string(&mut self) -> Result<String, JsonError> {
+        let mut buffer = String::new();
+        self.expect('"')?;
+
+        loop {
+            match self.next() {
+                Some('"') => break,
+                Some('\\') => {
+                    let pos = self.pos;
+                    match self.next() {
+                        Some('"') => buffer.push('"'),
+                        Some('\\') => buffer.push('\\'),
+                        Some('/') => buffer.push('/'),
+                        Some('b') => buffer.push('\u{8}'),
+                        Some('f') => buffer.push('\u{c}'),
+                        Some('n') => buffer.push('\n'),
+                        Some('r') => buffer.push('\r'),
+                        Some('t') => buffer.push('\t'),
+                        Some('u') => {
+                            let code: String = (0..4).filter_map(|_| self.next()).collect();
+                            let c = u32::from_str_radix(&code, 16).ok()
+                                .and_then(char::from_u32)
+                                .ok_or(JsonError::InvalidEscape(pos))?;
+                            buffer.push(c);
+                        },
+                        Some(_) => return Err(JsonError::InvalidEscape(pos)),
+                        None => return Err(JsonError::UnexpectedEnd),
+                    }
+                },
+                Some(c) => buffer.push(c),
+                None => return Err(JsonError::UnexpectedEnd),
+            }
+        }
+
+        Ok(buffer)
+    }
+
+    fn array(&mut self) -> Result<Json, JsonError> {
+        let mut values = Vec::new();
+        self.expect('[')?;
+        self.skip_whitespace();
+
+        if self.chars.peek() == Some(&']') {
+            self.next();
+            return Ok(Json::Array(values));
+        }
+
+        loop {
+            values.push(self.value()?);
+            self.skip_whitespace();
+
+            match self.next() {
+                Some(',') => (),
+                Some(']') => break,
+                Some(c) => return Err(JsonError::Unexpected(self.pos-1, c)),
+                None => return Err(JsonError::UnexpectedEnd),
+            }
+        }
+
+        Ok(Json::Array(values))
+    }
+
+    fn object(&mut self) -> Result<Json, JsonError> {
+        let mut pairs = Vec::new();
+        self.expect('{')?;
+        self.skip_whitespace();
+
+        if self.chars.peek() == Some(&'}') {
+            self.next();
+            return Ok(Json::Object(pairs));
+        }
+
+        loop {
+            self.skip_whitespace();
+            let key = self.string()?;
+            self.skip_whitespace();
+            self.expect(':')?;
+            pairs.push((key, self.value()?));
+            self.skip_whitespace();
+
+            match self.next() {
+                Some(',') => (),
+                Some('}') => break,
+                Some(c) => return Err(JsonError::Unexpected(self.pos-1, c)),
+                None => return Err(JsonError::UnexpectedEnd),
+            }
+        }
+
+        Ok(Json::Object(pairs))
+    }
+}
+
+impl From<bool> for Json {
+    fn from(b: bool) -> Self { Json::Bool(b) }
+}
+
+impl From<f64> for Json {
+    fn from(n: f64) -> Self { Json::Number(n) }
+}
+
+impl From<u8> for Json {
+    fn from(n: u8) -> Self { Json::Number(n as f64) }
+}
+
+impl From<usize> for Json {
+    fn from(n: usize) -> Self { Json::Number(n as f64) }
+}
+
+impl From<&str> for Json {
+    fn from(s: &str) -> Self { Json::String(s.to_string()) }
+}
+
+impl From<String> for Json {
+    fn from(s: String) -> Self { Json::String(s) }
+}
+
+impl<T: Into<Json>> From<Option<T>> for Json {
+    fn from(o: Option<T>) -> Self {
+        match o {
+            Some(v) => v.into(),
+            None => Json::Null,
+        }
+    }
+}
+
+impl<T: Into<Json>> From<Vec<T>> for Json {
+    fn from(v: Vec<T>) -> Self {
+        Json::Array(v.into_iter().map(|e| e.into()).collect())
+    }
+}
+
+impl<'a> From<&'a MetadataValue> for Json {
+    fn from(value: &'a MetadataValue) -> Self {
+        use MetadataValue::*;
+        match value {
+            String(s) => Json::String(s.clone()),
+            I16(n) => Json::Number(*n as f64),
+            I32(n) => Json::Number(*n as f64),
+            I64(n) => Json::Number(*n as f64),
+            U8(n) => Json::Number(*n as f64),
+            U16(n) => Json::Number(*n as f64),
+            U32(n) => Json::Number(*n as f64),
+            U64(n) => Json::Number(*n as f64),
+            F64(n) => Json::Number(*n),
+            Bool(b) => Json::Bool(*b),
+            Array(values) => Json::Array(values.iter().map(|v| v.into()).collect()),
+            Map(map) => {
+                let mut pairs: Vec<(std::string::String, Json)> = map.iter()
+                    .map(|(k, v)| (k.clone(), v.into()))
+                    .collect();
+                pairs.sort_by(|(a, _), (b, _)| a.cmp(b));
+                Json::Object(pairs)
+            },
+            Unsupported => Json::Null,
+        }
+    }
+}
+
+fn write_string(f: &mut fmt::Formatter, s: &str) -> fmt::Result {
+    f.write_str("\"")?;
+
+    for c in s.chars() {
+        match c {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            '\t' => f.write_str("\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+
+    f.write_str("\"")
+}
+
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Json::Null => f.write_str("null"),
+            Json::Bool(b) => write!(f, "{}", b),
+            Json::Number(n) => if n.is_finite() {
+                write!(f, "{}", n)
+            } else {
+                f.write_str("null")
+            },
+            Json::String(s) => write_string(f, s),
+            Json::Array(values) => {
+                f.write_str("[")?;
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 { f.write_str(",")?; }
+                    write!(f, "{}", value)?;
+                }
+                f.write_str("]")
+            },
+            Json::Object(pairs) => {
+                f.write_str("{")?;
+                for (i, (key, value)) in pairs.iter().enumerate() {
+                    if i > 0 { f.write_str(",")?; }
+                    write_string(f, key)?;
+                    write!(f, ":{}", value)?;
+                }
+                f.write_str("}")
+            },
+        }
+    }
+}
+
+#[test]
+fn test_json_display() {
+    let object = Json::object()
+        .with("text", "say \"hi\"\n")
+        .with("percentage", 42u8)
+        .with("ratio", 0.5)
+        .with("tooltip", None::<&str>)
+        .with("list", vec![true, false]);
+
+    assert_eq!(
+        object.to_string(),
+        r#"{"text":"say \"hi\"\n","percentage":42,"ratio":0.5,"tooltip":null,"list":[true,false]}"#
+    );
+}
+
+#[test]
+fn test_json_parse() {
+    let click = Json::parse(r#"{"name":"next","instance":"4","button":1,"modifiers":["Shift"],"x":1, "y":-2.5e0}"#).unwrap();
+    assert_eq!(click.get("name").and_then(Json::as_str), Some("next"));
+    assert_eq!(click.get("instance").and_then(Json::as_str), Some("4"));
+    assert_eq!(click.get("button").and_then(Json::as_f64), Some(1.0));
+    assert_eq!(click.get("y").and_then(Json::as_f64), Some(-2.5));
+
+    assert_eq!(Json::parse(r#""tab\tquote\"\u00e9""#).unwrap(), Json::String("tab\tquote\"é".to_string()));
+    assert!(Json::parse("[1, 2").is_err());
+    assert!(Json::parse("{} x").is_err());
+}
@@ -0,0 +1,166 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+/// Synced lyrics, read from an LRC file.
+#[derive(Debug, PartialEq)]
+pub struct Lyrics {
+    /// Sorted by time.
+    lines: Vec<(Duration, String)>,
+}
+
+impl Lyrics {
+    pub fn load(path: &Path) -> Option<Self> {
+        let content = fs::read(path).ok()?;
+        let content = String::from_utf8_lossy(&content);
+        let lyrics = Self::parse(content.trim_start_matches('\u{feff}'));
+
+        if lyrics.lines.is_empty() { None } else { Some(lyrics) }
+    }
+
+    pub fn parse(content: &str) -> Self {
+        let mut lines = Vec::<(i64, String)>::new();
+        let mut offset = 0i64;
+
+        for line in content.lines() {
+            let mut rest = line.trim();
+            let mut times = Vec::new();
+
+            // A line can have several timestamps, e.g. for a repeated chorus
+            while let Some((tag, after)) = rest.strip_prefix('[').and_then(|r| r.split_once(']')) {
+                if let Some(time) = parse_timestamp(tag) {
+                    times.push(time.as_millis() as i64);
+                } else if let Some(value) = tag.strip_prefix("offset:") {
+                    offset = value.trim().parse().unwrap_or(0);
+                }
+
+                rest = after;
+            }
+
+            let text = strip_word_timestamps(rest.trim());
+            for time in times {
+                lines.push((time, text.clone()));
+            }
+        }
+
+        // A positive offset shows the lyrics sooner
+        let mut lines: Vec<(Duration, String)> = lines.into_iter()
+            .map(|(time, text)| (Duration::from_millis((time - offset).max(0) as u64), text))
+            .collect();
+        lines.sort_by_key(|(time, _)| *time);
+
+        Self { lines }
+    }
+
+    /// The index of the line being sung at a position, if it's already started.
+    pub fn index_at(&self, position: Duration) -> Option<usize> {
+        self.lines.partition_point(|(time, _)| *time <= position).checked_sub(1)
+    }
+
+    pub fn line(&self, index: usize) -> Option<&str> {
+        self.lines.get(index).map(|(_, text)| text.as_str())
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.lines.len()
+    }
+}
+
+/// Finds the LRC file of a track, either next to it or in the lyrics directory
+/// as `file name.lrc` or `artist - title.lrc`.
+pub fn find(track: Option<&Path>, dir: Option<&Path>, artist: Option<&str>, title: Option<&str>) -> Option<PathBuf> {
+    let mut candidates = Vec::new();
+
+    if let Some(track) = track {
+        candidates.push(track.with_extension("lrc"));
+    }
+
+    if let Some(dir) = dir {
+        // Not with_extension, stems like `01. Intro` would lose their end
+        if let Some(stem) = track.and_then(Path::file_stem) {
+            let mut name = stem.to_os_string();
+            name.push(".lrc");
+            candidates.push(dir.join(name));
+        }
+
+        // Tags can have slashes, e.g. AC/DC
+        let name = match (artist, title) {
+            (Some(artist), Some(title)) => Some(format!("{} - {}.lrc", artist, title)),
+            (None, Some(title)) => Some(format!("{}.lrc", title)),
+            _ => None,
+        };
+
+        if let Some(name) = name {
+            candidates.push(dir.join(name.replace('/', "_")));
+        }
+    }
+
+    candidates.into_iter().find(|path| path.is_file())
+}
+
+/// Parses an LRC timestamp like `01:23.45`.
+fn parse_timestamp(tag: &str) -> Option<Duration> {
+    let (minutes, seconds) = tag.split_once(':')?;
+    let minutes = minutes.trim().parse::<u64>().ok()?;
+    let seconds = seconds.trim().parse::<f64>().ok()?;
+
+    if (0.0..60.0).contains(&seconds) {
+        Some(Duration::from_secs(minutes * 60) + Duration::from_secs_f64(seconds))
+    } else {
+        None
+    }
+}
+
+/// Removes the word timings of enhanced LRC, as in `<00:01.50>word`.
+fn strip_word_timestamps(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find('<') {
+        let tag = rest[start+1..].split_once('>')
+            .filter(|(tag, _)| parse_timestamp(tag).is_some());
+
+        match tag {
+            Some((_, after)) => {
+                result.push_str(&rest[..start]);
+                rest = after;
+            },
+            None => {
+                result.push_str(&rest[..start+1]);
+                rest = &rest[start+1..];
+            },
+        }
+    }
+
+    result.push_str(rest);
+    result.trim().to_string()
+}
+
+#[test]
+fn test_lyrics_parse() {
+    let lyrics = Lyrics::parse("\
+        [ar:Artist]\n\
+        [ti:Title]\n\
+        [offset:+500]\n\
+        [00:01.00]First line\n\
+        [00:05.50][01:00.00]Chorus\n\
+        [00:10.00]<00:10.00>Word <00:10.50>by <00:11.00>word\n\
+        [00:20.00]\n\
+        Not synced\n\
+    ");
+
+    assert_eq!(lyrics.line_count(), 5);
+    assert_eq!(lyrics.index_at(Duration::from_millis(400)), None);
+    assert_eq!(lyrics.index_at(Duration::from_millis(500)), Some(0));
+    assert_eq!(lyrics.line(0), Some("First line"));
+    assert_eq!(lyrics.index_at(Duration::from_secs(6)).and_then(|i| lyrics.line(i)), Some("Chorus"));
+    assert_eq!(lyrics.index_at(Duration::from_secs(12)).and_then(|i| lyrics.line(i)), Some("Word by word"));
+    assert_eq!(lyrics.index_at(Duration::from_secs(30)).and_then(|i| lyrics.line(i)), Some(""));
+    assert_eq!(lyrics.index_at(Duration::from_secs(90)).and_then(|i| lyrics.line(i)), Some("Chorus"));
+
+    assert_eq!(parse_timestamp("01:23.45"), Some(Duration::from_millis(83450)));
+    assert_eq!(parse_timestamp("ar:Artist"), None);
+    assert_eq!(strip_word_timestamps("a <b> c"), "a <b> c");
+}
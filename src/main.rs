@@ -1,13 +1,15 @@
 use std::{
+    collections::HashMap,
     env,
     fs::File,
-    io::{Read, Write, stdout},
+    io::{BufRead, Read, Write, stdin, stdout},
     path::{Path, PathBuf},
     sync::{
         atomic::{
             AtomicBool,
             Ordering
         },
+        mpsc::{self, Receiver},
         Arc,
     },
     thread,
@@ -20,19 +22,25 @@ use crossterm::{
     event::DisableMouseCapture,
     execute,
 };
-use mpris::{DBusError, Player, PlayerFinder, PlaybackStatus, Metadata};
+use mpris::{DBusError, LoopStatus, Player, PlayerFinder, PlaybackStatus, Metadata, MetadataValue};
+use art::ArtCache;
 use formatting::*;
+use icons::IconTheme;
+use json::Json;
+use lyrics::Lyrics;
+use pattern::Pattern;
 
+mod art;
 mod formatting;
+mod icons;
+mod json;
+mod lyrics;
+mod pattern;
 
 const DEBUG_BUILD: bool = cfg!(debug_assertions);
-const PLAY_ICON: &'static str = "\u{f144}";
-const PAUSE_ICON: &'static str = "\u{f28b}";
-const STOPPED_ICON: &'static str = "\u{f28d}";
-const PREV_ICON: &'static str = "\u{f04a}";
-const NEXT_ICON: &'static str = "\u{f04e}";
-const EMPTY_MSG: &'static str = "\u{f057} no music playing";
-#[cfg(not(debug_assertions))] const EMPTY_CHAR: char = '\u{feff}';
+const EMPTY_MSG: &'static str = "no music playing";
+const MISSING_TEXT: &'static str = "N/A";
+const EMPTY_CHAR: char = '\u{feff}';
 const PIPE_PATH: &'static str = concat!("/tmp/cornetroll.", env!("USER"));
 
 #[cfg(debug_assertions)]
@@ -44,31 +52,48 @@ const DEFAULT_META_FORMAT: &'static str = "<[artist] - >[title]";
 const DEFAULT_INFO_SETTINGS: (bool, bool) = (true, true);
 const DEFAULT_META_SETTINGS: (u8, u8) = (32, 10);
 const DEFAULT_TIME_SETTINGS: (bool, bool) = (true, false);
+const DEFAULT_VOLUME_SETTINGS: (bool, bool) = (false, true);
+const DEFAULT_PROGRESS_SETTINGS: (u8, &'static str) = (10, "smooth");
+const DEFAULT_SEEK_SETTINGS: (u8, u8) = (15, 30);
+const DEFAULT_ART_SETTINGS: u8 = 0;
+const DEFAULT_LYRICS_SETTINGS: (u8, u8) = (32, 10);
+const DEFAULT_PLAYERS_SETTINGS: (u8, bool) = (10, true);
+const PROGRESS_SMOOTH_GLYPHS: &[char] = &['▏', '▎', '▍', '▌', '▋', '▊', '▉', '█'];
+const VOLUME_STEP: f64 = 0.05;
 
 const COMMAND_PLAY: &'static str = "play";
 const COMMAND_PAUSE: &'static str = "pause";
 const COMMAND_STOP: &'static str = "stop";
+const COMMAND_RAISE: &'static str = "raise";
+const COMMAND_QUIT: &'static str = "quit";
 const COMMAND_PREV: &'static str = "prev";
 const COMMAND_NEXT: &'static str = "next";
 const COMMAND_PREV_PLAYER: &'static str = "prev-player";
 const COMMAND_NEXT_PLAYER: &'static str = "next-player";
 const COMMAND_PLAY_PAUSE: &'static str = "play-pause";
+const COMMAND_FOCUS: &'static str = "focus";
+const COMMAND_VOLUME: &'static str = "volume";
+const COMMAND_VOLUME_UP: &'static str = "volume-up";
+const COMMAND_VOLUME_DOWN: &'static str = "volume-down";
+const COMMAND_MUTE: &'static str = "mute";
+const COMMAND_SEEK: &'static str = "seek";
+const COMMAND_SEEK_TO: &'static str = "seek-to";
+const COMMAND_TOGGLE_SHUFFLE: &'static str = "toggle-shuffle";
+const COMMAND_CYCLE_LOOP: &'static str = "cycle-loop";
 
 const COMMANDS: &[&'static str] = &[
     COMMAND_PLAY, COMMAND_PAUSE, COMMAND_STOP, COMMAND_PREV,
     COMMAND_NEXT, COMMAND_PREV_PLAYER, COMMAND_NEXT_PLAYER,
-    COMMAND_PLAY_PAUSE,
+    COMMAND_PLAY_PAUSE, COMMAND_VOLUME, COMMAND_VOLUME_UP,
+    COMMAND_VOLUME_DOWN, COMMAND_MUTE, COMMAND_SEEK, COMMAND_SEEK_TO,
+    COMMAND_TOGGLE_SHUFFLE, COMMAND_CYCLE_LOOP, COMMAND_FOCUS,
+    COMMAND_RAISE, COMMAND_QUIT,
 ];
 
 macro_rules! str_len {
     ($s:expr) => { $s.chars().count() }
 }
 
-enum Either<L, R> {
-    Left(L),
-    Right(R),
-}
-
 struct Scroller {
     content: String,
     buffer: String,
@@ -82,18 +107,66 @@ struct Scroller {
 struct Config {
     display_format: Vec<DisplayFormat>,
     meta_format: Vec<MetaFormat>,
+    /// Formats of named metadata blocks, falling back to `meta_format`.
+    named_meta_formats: HashMap<String, Vec<MetaFormat>>,
+    /// Formats of the players matching a pattern, the first match winning.
+    player_formats: Vec<PlayerFormat>,
     refresh_wait: u8,
     markup_type: MarkupType,
     empty_msg: String,
+    /// Shown for missing tags and times.
+    missing_text: String,
+    lyrics_dir: Option<PathBuf>,
+    icons: IconTheme,
+}
+
+/// Formats for the players matching a pattern, instead of the global ones.
+struct PlayerFormat {
+    /// A glob matched against the whole identity or bus name, ignoring case.
+    pattern: Pattern,
+    display_format: Option<Vec<DisplayFormat>>,
+    meta_format: Option<Vec<MetaFormat>>,
 }
 
 #[derive(Copy, Clone, Eq, PartialEq)]
 enum MarkupType {
     Polybar,
     Yuck,
+    Waybar,
+    I3bar,
+    Json,
+    Eww,
+    Lemonbar,
+    Xmobar,
+    Tmux,
+    Yambar,
     Plain,
 }
 
+/// A display block rendered for the current tick, before any markup is applied.
+struct RenderedBlock {
+    name: String,
+    text: String,
+    icon: Option<String>,
+    actions: Vec<(MouseButton, String)>,
+    /// Separately clickable parts, like the cells of a progress bar.
+    parts: Vec<RenderedBlock>,
+}
+
+/// What the program was asked to do by the command line.
+enum Mode {
+    /// Send a command to the running instance.
+    Command(String),
+    /// Run the command of a clicked tmux range, on the first player when
+    /// there's no running instance to send it to.
+    Range(String, Config),
+    /// Send every command read from stdin to the running instance.
+    ReadCommands,
+    /// Print the display a single time and exit.
+    Once(Config),
+    Controller(Config),
+}
+
 struct PlayerStatus {
     bin_path: PathBuf,
     config: Config,
@@ -101,13 +174,66 @@ struct PlayerStatus {
     players: Vec<Player>,
     display_buffer: String,
     info_scroller: Scroller,
-    meta_scroller: Scroller,
+    /// One for every metadata block name in each display format, by the index
+    /// of the player format it's from (`None` being the global one) and name
+    /// (`None` being unnamed blocks).
+    meta_scrollers: Vec<(Option<usize>, Option<String>, Scroller)>,
+    /// One for every display format with a lyrics block, as above.
+    lyrics_scrollers: Vec<(Option<usize>, Scroller)>,
     current_idx: usize,
     refresh_wait: u8,
     last_display: String,
+    /// The blocks last sent to i3bar, indexed by their instance.
+    click_blocks: Vec<RenderedBlock>,
+    /// Volumes before muting, by bus name.
+    muted_volumes: HashMap<String, f64>,
+    art_cache: ArtCache,
+    /// The track id and URL the lyrics were loaded for.
+    lyrics_track: Option<(String, Option<String>)>,
+    lyrics: Option<Lyrics>,
+    /// The bus name and metadata the metadata scrollers were last built from,
+    /// so the filters only run again when the track changes.
+    meta_track: Option<(String, Metadata)>,
     _player_id: usize,
 }
 
+impl Config {
+    /// The display format of a player, its own if it has one.
+    fn display_format(&self, player: Option<&Player>) -> &[DisplayFormat] {
+        self.display_format_idx(player)
+            .and_then(|i| self.player_formats[i].display_format.as_deref())
+            .unwrap_or(&self.display_format)
+    }
+
+    /// The index of the player format a player's display format comes from,
+    /// `None` if it uses the global one.
+    fn display_format_idx(&self, player: Option<&Player>) -> Option<usize> {
+        let player = player?;
+        self.player_formats.iter()
+            .position(|format| format.display_format.is_some() && format.matches(player.identity(), player.bus_name()))
+    }
+
+    /// The format of a metadata block, named formats coming before the player's.
+    fn meta_format(&self, player: Option<&Player>, name: Option<&str>) -> &[MetaFormat] {
+        name.and_then(|name| self.named_meta_formats.get(name))
+            .map(Vec::as_slice)
+            .or_else(|| player.and_then(|player| self.player_format(player, |format| format.meta_format.as_deref())))
+            .unwrap_or(&self.meta_format)
+    }
+
+    fn player_format<'a, T: ?Sized>(&'a self, player: &Player, get: impl Fn(&'a PlayerFormat) -> Option<&'a T>) -> Option<&'a T> {
+        self.player_formats.iter()
+            .filter(|format| format.matches(player.identity(), player.bus_name()))
+            .find_map(get)
+    }
+}
+
+impl PlayerFormat {
+    fn matches(&self, identity: &str, bus_name: &str) -> bool {
+        self.pattern.is_match(&identity.to_lowercase()) || self.pattern.is_match(&bus_name.to_lowercase())
+    }
+}
+
 impl PlayerStatus {
     pub fn new(config: Config) -> Self {
         let mut me = Self {
@@ -116,10 +242,17 @@ impl PlayerStatus {
             players: Vec::new(),
             display_buffer: String::new(),
             info_scroller: Scroller::new(0, 0),
-            meta_scroller: Scroller::new(0, 0),
+            meta_scrollers: Vec::new(),
+            lyrics_scrollers: Vec::new(),
             current_idx: 0,
             refresh_wait: 0,
             last_display: String::new(),
+            click_blocks: Vec::new(),
+            muted_volumes: HashMap::new(),
+            art_cache: ArtCache::new(),
+            lyrics_track: None,
+            lyrics: None,
+            meta_track: None,
             _player_id: 0,
             config,
         };
@@ -136,15 +269,29 @@ impl PlayerStatus {
     }
 
     fn init_scrollers(&mut self) {
-        for block in &self.config.display_format {
-            match block {
-                DisplayFormat::PlayerInfo(_, _) => {
-                    self.info_scroller = Scroller::new(10, 6);
-                },
-                DisplayFormat::Metadata(buffer_size, scroller_wait) => {
-                    self.meta_scroller = Scroller::new(*buffer_size, *scroller_wait);
-                },
-                _ => (),
+        // Any player could become the current one, and each format gets its
+        // own scrollers so their sizes don't clash
+        let formats = self.config.player_formats.iter().enumerate()
+            .filter_map(|(i, format)| Some((Some(i), format.display_format.as_deref()?)))
+            .chain([(None, self.config.display_format.as_slice())]);
+
+        for (format, blocks) in formats {
+            for block in all_blocks(blocks) {
+                match block.block() {
+                    DisplayFormat::PlayerInfo(_, _) => {
+                        self.info_scroller = Scroller::new(10, 6);
+                    },
+                    // Blocks with the same name share a scroller
+                    DisplayFormat::Metadata(buffer_size, scroller_wait, name)
+                        if !self.meta_scrollers.iter().any(|(f, n, _)| *f == format && n == name) =>
+                    {
+                        self.meta_scrollers.push((format, name.clone(), Scroller::new(*buffer_size, *scroller_wait)));
+                    },
+                    DisplayFormat::Lyrics(buffer_size, scroller_wait) if !self.lyrics_scrollers.iter().any(|(f, _)| *f == format) => {
+                        self.lyrics_scrollers.push((format, Scroller::new(*buffer_size, *scroller_wait)));
+                    },
+                    _ => (),
+                }
             }
         }
     }
@@ -163,7 +310,10 @@ impl PlayerStatus {
                 self.info_scroller.update();
             }
             if let Ok(meta) = self.current_player().get_metadata() {
-                if self.meta_scroller.is_initialized() {
+                self.update_art(&meta);
+                self.update_lyrics(&meta);
+
+                if !self.meta_scrollers.is_empty() {
                     self.update_meta(meta);
                 }
             }
@@ -172,10 +322,42 @@ impl PlayerStatus {
         self.display();
     }
 
+    /// The index of the current player's display format, see `Config::display_format_idx`.
+    fn format_idx(&self) -> Option<usize> {
+        self.config.display_format_idx(self.players.get(self.current_idx))
+    }
+
+    fn meta_scroller(&self, name: Option<&str>) -> Option<&Scroller> {
+        let format = self.format_idx();
+        self.meta_scrollers.iter()
+            .find(|(f, n, _)| *f == format && n.as_deref() == name)
+            .map(|(_, _, scroller)| scroller)
+    }
+
+    /// The unnamed metadata scroller, or the first named one if the format has
+    /// only named blocks.
+    fn tooltip_scroller(&self) -> Option<&Scroller> {
+        let format = self.format_idx();
+        self.meta_scroller(None).or_else(|| self.meta_scrollers.iter()
+            .find(|(f, _, _)| *f == format)
+            .map(|(_, _, scroller)| scroller))
+    }
+
+    fn lyrics_scroller(&self) -> Option<&Scroller> {
+        let format = self.format_idx();
+        self.lyrics_scrollers.iter()
+            .find(|(f, _)| *f == format)
+            .map(|(_, scroller)| scroller)
+    }
+
     fn current_player(&self) -> &Player {
         &self.players[self.current_idx]
     }
 
+    fn display_format(&self) -> &[DisplayFormat] {
+        self.config.display_format(self.players.get(self.current_idx))
+    }
+
     pub fn display(&mut self) {
         if self.players.len() > 0 {
             let status = match self.current_player().get_playback_status() {
@@ -188,102 +370,47 @@ impl PlayerStatus {
                 },
             };
 
-            self.display_buffer.clear();
-
-            if self.config.markup_type == MarkupType::Yuck {
-                self.display_buffer.push_str("(box :class \"cornetroll\" :space-evenly false :vexpand true");
+            if self.config.markup_type == MarkupType::Json {
+                let line = self.state_json(Some(status)).to_string();
+                self.print_flush(line);
+                return;
             }
 
-            for block in self.config.display_format.iter() {
-                let result = match block {
-                    DisplayFormat::Prev => self.action("prev", PREV_ICON),
-                    DisplayFormat::PlayPause => match status {
-                        PlaybackStatus::Playing => self.action("pause", PAUSE_ICON),
-                        _ => self.action("play", PLAY_ICON),
-                    },
-                    DisplayFormat::Next => self.action("next", NEXT_ICON),
-                    DisplayFormat::Status => self.text(match status {
-                        PlaybackStatus::Playing => PLAY_ICON.to_string(),
-                        PlaybackStatus::Paused => PAUSE_ICON.to_string(),
-                        PlaybackStatus::Stopped => STOPPED_ICON.to_string(),
-                    }),
-                    DisplayFormat::PlayerInfo(show_total, show_name) => {
-                        let mut info = String::new();
-                        info.push_str(&format!("{}", self.current_idx+1));
-
-                        if *show_total {
-                            info.push_str(&format!("/{}", self.players.len()));
-                        }
-
-                        if *show_name {
-                            info.push_str(": ");
-                            info.push_str(self.info_scroller.display());
-                        }
-
-                        self.text(info)
-                    },
-                    DisplayFormat::Metadata(_, _) => {
-                        self.text(self.meta_scroller.display().to_string())
-                    },
-                    DisplayFormat::Time(show_length, use_remaining) => {
-                        let mut time = String::new();
+            let blocks = self.render_blocks(status);
+            self.display_buffer.clear();
 
-                        #[inline]
-                        fn format_time(dur: Duration) -> String {
-                            format!("{:02}:{:02}", dur.as_secs()/60, dur.as_secs() % 60)
-                        }
+            if self.config.markup_type == MarkupType::I3bar {
+                let line = Json::Array(blocks.iter().enumerate().map(|(i, block)| Json::object()
+                    .with("name", block.name.as_str())
+                    .with("instance", i.to_string())
+                    .with("full_text", block.text.as_str())
+                    .with("separator", false)
+                    .with("separator_block_width", 0u8)
+                ).collect());
+
+                self.click_blocks = blocks;
+                self.print_flush(format!("{},", line));
+                return;
+            }
 
-                        let position = self.current_player().get_position();
-                        let length = self.current_player().get_metadata().unwrap().length();
-                        let remaining = if let Ok(p) = position {
-                            if let Some(l) = length { Some(l-p) }
-                            else { None }
-                        } else {
-                            None
-                        };
+            if self.config.markup_type == MarkupType::Eww {
+                let line = self.eww_json(Some(status), &blocks).to_string();
+                self.print_flush(line);
+                return;
+            }
 
-                        if *show_length {
-                            if let Ok(v) = position {
-                                time.push_str(&format_time(v));
-                            } else {
-                                time.push_str("N/A");
-                            }
-                            time.push_str("/");
-
-                            if *use_remaining {
-                                if let Some(v) = remaining {
-                                    time.push_str(&format_time(v));
-                                } else {
-                                    time.push_str("N/A");
-                                }
-                            } else {
-                                if let Some(v) = length {
-                                    time.push_str(&format_time(v));
-                                } else {
-                                    time.push_str("N/A");
-                                }
-                            }
-                        } else {
-                            if *use_remaining {
-                                if let Some(v) = remaining {
-                                    time.push_str(&format_time(v));
-                                } else {
-                                    time.push_str("N/A");
-                                }
-                            } else {
-                                if let Ok(v) = position {
-                                    time.push_str(&format_time(v));
-                                } else {
-                                    time.push_str("N/A");
-                                }
-                            }
-                        }
+            if self.config.markup_type == MarkupType::Yambar {
+                let tags = self.yambar_tags(status, &blocks);
+                self.print_flush(tags);
+                return;
+            }
 
-                        self.text(time)
-                    },
-                    DisplayFormat::String(s) => self.text(s),
-                };
+            if self.config.markup_type == MarkupType::Yuck {
+                self.display_buffer.push_str("(box :class \"cornetroll\" :space-evenly false :vexpand true");
+            }
 
+            for block in blocks.iter() {
+                let result = self.markup(block);
                 self.display_buffer.push_str(&result);
             }
 
@@ -291,173 +418,599 @@ impl PlayerStatus {
                 self.display_buffer.push(')');
             }
 
-            self.print_flush(self.display_buffer.clone().trim_end());
+            if self.config.markup_type == MarkupType::Waybar {
+                let line = Json::object()
+                    .with("text", self.display_buffer.trim_end())
+                    .with("tooltip", self.escape(self.tooltip_scroller().map(Scroller::content).unwrap_or_default()))
+                    .with("class", status_name(status))
+                    .with("percentage", self.progress().map(|p| (p * 100.0).round()))
+                    .to_string();
+
+                self.print_flush(line);
+            } else {
+                self.print_flush(self.display_buffer.clone().trim_end());
+            }
+        } else if self.config.markup_type == MarkupType::Json {
+            let line = self.state_json(None).to_string();
+            self.print_flush(line);
+        } else if self.config.markup_type == MarkupType::Eww {
+            let line = self.eww_json(None, &[]).to_string();
+            self.print_flush(line);
+        } else if self.config.markup_type == MarkupType::Yambar {
+            let tags = [
+                yambar_tag("players", "int", 0),
+                yambar_tag("playing", "bool", false),
+                yambar_tag("status", "string", ""),
+                yambar_tag("empty_msg", "string", &self.config.empty_msg),
+            ].concat();
+            self.print_flush(tags);
+        } else if self.config.markup_type == MarkupType::Waybar {
+            let line = Json::object()
+                .with("text", self.config.empty_msg.as_str())
+                .with("class", "empty")
+                .to_string();
+
+            self.print_flush(line);
+        } else if self.config.markup_type == MarkupType::I3bar {
+            let line = Json::Array(vec![Json::object()
+                .with("name", "empty")
+                .with("full_text", self.config.empty_msg.as_str())
+            ]);
+
+            self.click_blocks.clear();
+            self.print_flush(format!("{},", line));
         } else {
             self.print_flush(self.text(&self.config.empty_msg))
         }
     }
 
-    fn update_meta(&mut self, meta: Metadata) {
-        const EMPTY_TAG: &str = "N/A";
-
-        /// Tags are only Some if there's at least a non-empty string.
-        macro_rules! validate_tag {
-            ($tag:expr) => {
-                match $tag {
-                    Some(t) => if t.len() > 0 { Some(t) } else { None },
-                    n => n,
-                }
-            };
+    /// The whole state of the current player, for the `json` markup type.
+    fn state_json(&self, status: Option<PlaybackStatus>) -> Json {
+        let status = match status {
+            Some(status) => status,
+            None => return Json::object()
+                .with("player", Json::Null)
+                .with("index", Json::Null)
+                .with("total", 0u8),
+        };
 
-            (list, $tag:expr) => {
-                match $tag {
-                    Some(list) => {
-                        if list.len() > 0 {
-                            if list[0].len() > 0 {
-                                Some(list.iter().map(|e| *e).collect())
-                            } else { None }
-                        } else { None }
-                    },
-                    None => None,
-                }
+        let player = self.current_player();
+        let meta = player.get_metadata().ok();
+
+        // Sorted, otherwise every line would be different
+        let mut tags: Vec<(&str, &MetadataValue)> = meta.iter().flat_map(|m| m.iter()).collect();
+        tags.sort_by_key(|(key, _)| *key);
+
+        let scroller = |scroller: &Scroller| if scroller.is_initialized() {
+            Json::from(scroller.visible())
+        } else {
+            Json::Null
+        };
+
+        Json::object()
+            .with("player", Json::object()
+                .with("identity", player.identity())
+                .with("bus_name", player.bus_name())
+            )
+            // Counting from 1, like `focus` and the `[info]` block
+            .with("index", self.current_idx+1)
+            .with("total", self.players.len())
+            .with("status", status_name(status))
+            .with("metadata", Json::Object(tags.into_iter()
+                .map(|(key, value)| (key.to_string(), value.into()))
+                .collect()
+            ))
+            .with("position", player.get_position().ok().map(|p| p.as_secs_f64()))
+            .with("length", meta.as_ref().and_then(|m| m.length()).map(|l| l.as_secs_f64()))
+            .with("volume", player.get_volume().ok())
+            .with("shuffle", player.get_shuffle().ok())
+            .with("loop", player.get_loop_status().ok().map(|status| match status {
+                LoopStatus::None => "none",
+                LoopStatus::Track => "track",
+                LoopStatus::Playlist => "playlist",
+            }))
+            .with("scrollers", Json::Object(
+                [
+                    ("info".to_string(), scroller(&self.info_scroller)),
+                    ("metadata".to_string(), self.meta_scroller(None).map(scroller).unwrap_or(Json::Null)),
+                    ("lyrics".to_string(), self.lyrics_scroller().map(scroller).unwrap_or(Json::Null)),
+                ].into_iter()
+                .chain(self.meta_scrollers.iter()
+                    .filter(|(format, _, _)| *format == self.format_idx())
+                    .filter_map(|(_, name, s)| name.as_ref().map(|name| (format!("metadata#{}", name), scroller(s))))
+                )
+                .collect()
+            ))
+            .with("lyrics", match &self.lyrics {
+                Some(lyrics) => Json::object()
+                    .with("line", self.lyrics_line(0))
+                    .with("next", self.lyrics_line(1))
+                    .with("index", player.get_position().ok().and_then(|p| lyrics.index_at(p)).map(|i| i+1))
+                    .with("total", lyrics.line_count()),
+                None => Json::Null,
+            })
+    }
+
+    /// The blocks and players for the `eww` markup type, to be used by a `deflisten`.
+    fn eww_json(&self, status: Option<PlaybackStatus>, blocks: &[RenderedBlock]) -> Json {
+        let players: Vec<_> = self.players.iter()
+            .map(|player| (player.identity(), player.bus_name(), player.get_playback_status().ok()))
+            .collect();
+
+        eww_state(&self.bin_path, status, blocks, &players, self.current_idx, &self.config.empty_msg)
+    }
+
+    /// Every block and metadata tag as yambar `script` tags, ending with the
+    /// empty line that marks the end of a transaction.
+    fn yambar_tags(&self, status: PlaybackStatus, blocks: &[RenderedBlock]) -> String {
+        let mut tags = String::new();
+
+        macro_rules! tag {
+            ($name:expr, $kind:expr, $value:expr) => {
+                tags.push_str(&yambar_tag($name, $kind, $value));
             };
         }
 
-        struct Tags<'a> {
-            artists: Option<Vec<&'a str>>,
-            album_name: Option<&'a str>,
-            album_artists: Option<Vec<&'a str>>,
-            title: Option<&'a str>,
-            track: Option<i32>,
+        tag!("players", "int", self.players.len());
+        tag!("index", "int", self.current_idx+1);
+        tag!("player", "string", self.current_player().identity());
+        tag!("status", "string", status_name(status));
+        tag!("playing", "bool", status == PlaybackStatus::Playing);
+        tag!("progress", "range:0-100", yambar_percent(self.progress()));
+
+        if let Ok(meta) = self.current_player().get_metadata() {
+            let artists = meta.artists().unwrap_or_default();
+            let album_artists = meta.album_artists().unwrap_or_default();
+
+            tag!("artist", "string", artists.first().unwrap_or(&""));
+            tag!("artists", "string", artists.join(", "));
+            tag!("album", "string", meta.album_name().unwrap_or(""));
+            tag!("album_artist", "string", album_artists.first().unwrap_or(&""));
+            tag!("title", "string", meta.title().unwrap_or(""));
+            tag!("track", "int", meta.track_number().unwrap_or(0));
         }
 
-        let tags = &Tags {
-            artists: validate_tag!(list, meta.artists()),
-            album_name: validate_tag!(meta.album_name()),
-            album_artists: validate_tag!(list, meta.album_artists()),
-            title: validate_tag!(meta.title()),
-            track: meta.track_number(),
-        };
+        tags.push_str(&yambar_block_tags(blocks));
+        tags
+    }
 
-        let mut content = String::new();
+    /// Renders every display block for the current tick, leaving the markup to
+    /// the caller.
+    fn render_blocks(&self, status: PlaybackStatus) -> Vec<RenderedBlock> {
+        let mut blocks = Vec::new();
+        self.render_format(self.display_format(), status, &mut blocks);
+        blocks
+    }
 
-        // Optionals render Strings before and after the first valid block
-        fn build_content(content: &mut String, tags: &Tags, blocks: &Vec<MetaFormat>, optional: bool) {
-            let mut flush_buffer = String::new();
-            let mut flush = false;
+    /// Renders the blocks of a display format, or of one of its sections.
+    fn render_format(&self, format: &[DisplayFormat], status: PlaybackStatus, blocks: &mut Vec<RenderedBlock>) {
+        let icons = &self.config.icons;
 
-            macro_rules! flushtag {
-                (buffer) => {
-                    if flush_buffer.len() > 0 && flush {
-                        content.extend(flush_buffer.chars());
-                        flush_buffer.clear();
+        for block in format.iter() {
+            let rendered = match block.block() {
+                DisplayFormat::Prev(icon) => {
+                    RenderedBlock::action("prev", COMMAND_PREV, &self.icon(icon, &icons.prev))
+                },
+                DisplayFormat::PlayPause(play_icon, pause_icon) => match status {
+                    PlaybackStatus::Playing => RenderedBlock::action("play-pause", COMMAND_PAUSE, &self.icon(pause_icon, &icons.pause)),
+                    _ => RenderedBlock::action("play-pause", COMMAND_PLAY, &self.icon(play_icon, &icons.play)),
+                },
+                DisplayFormat::Next(icon) => {
+                    RenderedBlock::action("next", COMMAND_NEXT, &self.icon(icon, &icons.next))
+                },
+                DisplayFormat::Stop(icon) => {
+                    RenderedBlock::action("stop", COMMAND_STOP, &self.icon(icon, &icons.stopped))
+                },
+                DisplayFormat::Raise(icon) => match self.current_player().can_raise() {
+                    Ok(true) => RenderedBlock::action("raise", COMMAND_RAISE, &self.icon(icon, &icons.raise)),
+                    _ => RenderedBlock::text("raise", String::new()),
+                },
+                DisplayFormat::Quit(icon) => match self.current_player().can_quit() {
+                    Ok(true) => RenderedBlock::action("quit", COMMAND_QUIT, &self.icon(icon, &icons.quit)),
+                    _ => RenderedBlock::text("quit", String::new()),
+                },
+                DisplayFormat::Status(play_icon, pause_icon, stopped_icon) => RenderedBlock::icon("status", &match status {
+                    PlaybackStatus::Playing => self.icon(play_icon, &icons.play),
+                    PlaybackStatus::Paused => self.icon(pause_icon, &icons.pause),
+                    PlaybackStatus::Stopped => self.icon(stopped_icon, &icons.stopped),
+                }),
+                DisplayFormat::PlayerInfo(show_total, show_name) => {
+                    let mut info = String::new();
+                    info.push_str(&format!("{}", self.current_idx+1));
+
+                    if *show_total {
+                        info.push_str(&format!("/{}", self.players.len()));
                     }
-                };
 
-                (flush) => {
-                    if !flush { flush = true; }
-                };
+                    if *show_name {
+                        info.push_str(": ");
+                        info.push_str(&self.escape(self.info_scroller.display()));
+                    }
 
-                (unflush) => {
-                    if flush {
-                        flush = false;
-                        if flush_buffer.len() > 0 { flush_buffer.clear(); }
+                    RenderedBlock::text("info", info)
+                },
+                DisplayFormat::Metadata(_, _, name) => {
+                    let text = self.meta_scroller(name.as_deref()).map(Scroller::display).unwrap_or_default();
+
+                    match name {
+                        Some(name) => RenderedBlock::text(&format!("metadata-{}", name), self.escape(text)),
+                        None => RenderedBlock::text("metadata", self.escape(text)),
                     }
-                };
+                },
+                DisplayFormat::Time(show_length, use_remaining) => {
+                    let missing = &self.config.missing_text;
+                    let mut time = String::new();
 
-                ($tag:expr) => {
-                    if optional {
-                        if $tag.is_some() {
-                            flushtag!(flush);
-                            flushtag!(buffer);
-                            content.push_str($tag.unwrap());
+                    #[inline]
+                    fn format_time(dur: Duration) -> String {
+                        format!("{:02}:{:02}", dur.as_secs()/60, dur.as_secs() % 60)
+                    }
+
+                    let position = self.current_player().get_position();
+                    let length = self.current_player().get_metadata().unwrap().length();
+                    let remaining = if let Ok(p) = position {
+                        if let Some(l) = length { Some(l-p) }
+                        else { None }
+                    } else {
+                        None
+                    };
+
+                    if *show_length {
+                        if let Ok(v) = position {
+                            time.push_str(&format_time(v));
                         } else {
-                            flushtag!(unflush);
+                            time.push_str(missing);
                         }
-                    } else {
-                        content.push_str($tag.unwrap_or(EMPTY_TAG));
-                    }
-                };
+                        time.push_str("/");
 
-                (number, $tag:expr) => {
-                    if optional {
-                        if $tag.is_some() {
-                            flushtag!(flush);
-                            flushtag!(buffer);
-                            content.push_str(&format!("{}", $tag.unwrap()));
+                        if *use_remaining {
+                            if let Some(v) = remaining {
+                                time.push_str(&format_time(v));
+                            } else {
+                                time.push_str(missing);
+                            }
                         } else {
-                            flushtag!(unflush);
+                            if let Some(v) = length {
+                                time.push_str(&format_time(v));
+                            } else {
+                                time.push_str(missing);
+                            }
                         }
                     } else {
-                        if let Some(n) = $tag {
-                            content.extend(format!("{}", n).chars());
+                        if *use_remaining {
+                            if let Some(v) = remaining {
+                                time.push_str(&format_time(v));
+                            } else {
+                                time.push_str(missing);
+                            }
+                        } else {
+                            if let Ok(v) = position {
+                                time.push_str(&format_time(v));
+                            } else {
+                                time.push_str(missing);
+                            }
                         }
                     }
-                };
 
-                (first, $tag:expr) => {
-                    if optional {
-                        if $tag.is_some() {
-                            flushtag!(flush);
-                            flushtag!(buffer);
-                            content.push_str($tag.clone().unwrap()[0]);
-                        } else {
-                            flushtag!(unflush);
+                    RenderedBlock::text("time", time)
+                },
+                DisplayFormat::Volume(show_icon, show_percentage) => {
+                    let volume = self.current_player().get_volume().ok();
+                    let icon = self.escape(match volume {
+                        Some(v) if v <= 0.0 => &icons.volume_off,
+                        Some(v) if v < 0.5 => &icons.volume_low,
+                        _ => &icons.volume_high,
+                    });
+
+                    let mut text = String::new();
+
+                    if *show_icon {
+                        text.push_str(&icon);
+                    }
+
+                    if *show_percentage {
+                        if *show_icon { text.push(' '); }
+
+                        match volume {
+                            Some(v) => text.push_str(&format!("{}%", (v * 100.0).round())),
+                            None => text.push_str(&self.config.missing_text),
                         }
-                    } else {
-                        content.push_str(if let Some(list) = &$tag { list[0] } else { EMPTY_TAG });
                     }
-                };
 
-                (join, $tag:expr) => {
-                    if optional {
-                        if $tag.is_some() {
-                            flushtag!(flush);
-                            flushtag!(buffer);
-                            content.extend($tag.clone().unwrap().join(", ").chars());
+                    RenderedBlock { name: "volume".to_string(), text, icon: Some(icon), actions: Vec::new(), parts: Vec::new() }
+                        .with(MouseButton::Left, COMMAND_MUTE)
+                        .with(MouseButton::ScrollUp, COMMAND_VOLUME_UP)
+                        .with(MouseButton::ScrollDown, COMMAND_VOLUME_DOWN)
+                },
+                DisplayFormat::Progress(width, style) => {
+                    let width = (*width).max(1) as usize;
+                    let cells = progress_cells(self.progress().unwrap_or(0.0), width, style);
+
+                    // Every cell seeks to where it starts
+                    let parts = cells.iter().enumerate().map(|(i, cell)| {
+                        RenderedBlock::text("progress", cell.clone())
+                            .with(MouseButton::Left, &format!("{} {}%", COMMAND_SEEK_TO, i * 100 / width))
+                    }).collect();
+
+                    RenderedBlock { name: "progress".to_string(), text: cells.concat(), icon: None, actions: Vec::new(), parts }
+                },
+                DisplayFormat::Shuffle(on_icon, off_icon) => {
+                    let icon = match self.current_player().get_shuffle() {
+                        Ok(true) => self.icon(on_icon, &icons.shuffle_on),
+                        _ => self.icon(off_icon, &icons.shuffle_off),
+                    };
+
+                    RenderedBlock::icon("shuffle", &icon)
+                        .with(MouseButton::Left, COMMAND_TOGGLE_SHUFFLE)
+                },
+                DisplayFormat::Loop(none_icon, track_icon, playlist_icon) => {
+                    let icon = match self.current_player().get_loop_status() {
+                        Ok(LoopStatus::Track) => self.icon(track_icon, &icons.loop_track),
+                        Ok(LoopStatus::Playlist) => self.icon(playlist_icon, &icons.loop_playlist),
+                        _ => self.icon(none_icon, &icons.loop_none),
+                    };
+
+                    RenderedBlock::icon("loop", &icon)
+                        .with(MouseButton::Left, COMMAND_CYCLE_LOOP)
+                },
+                DisplayFormat::SeekBack(seconds, icon) => {
+                    let command = format!("{} -{}", COMMAND_SEEK, seconds);
+                    RenderedBlock::action("seek-back", &command, &self.icon(icon, &icons.seek_back))
+                },
+                DisplayFormat::SeekForward(seconds, icon) => {
+                    let command = format!("{} +{}", COMMAND_SEEK, seconds);
+                    RenderedBlock::action("seek-fwd", &command, &self.icon(icon, &icons.seek_forward))
+                },
+                DisplayFormat::Players(name_size, show_status) => {
+                    let mut parts = Vec::new();
+
+                    for (i, player) in self.players.iter().enumerate() {
+                        let mut label = String::new();
+
+                        if *show_status {
+                            label.push_str(match player.get_playback_status() {
+                                Ok(PlaybackStatus::Playing) => &icons.play,
+                                Ok(PlaybackStatus::Paused) => &icons.pause,
+                                _ => &icons.stopped,
+                            });
+                            label.push(' ');
+                        }
+
+                        // Keep the label on a single line
+                        let name = player.identity().chars()
+                            .map(|c| if c.is_control() { ' ' } else { c });
+                        if *name_size > 0 {
+                            label.extend(name.take(*name_size as usize));
                         } else {
-                            flushtag!(unflush);
+                            label.extend(name);
                         }
-                        if let Some(list) = &$tag {
-                            content.extend(list.join(", ").chars());
+
+                        // Same width either way, so the labels don't move around
+                        let label = if i == self.current_idx {
+                            format!("[{}]", self.escape(&label))
                         } else {
-                            content.push_str(EMPTY_TAG);
+                            format!(" {} ", self.escape(&label))
                         };
+
+                        parts.push(RenderedBlock::text("players", label)
+                            .with(MouseButton::Left, &format!("{} {}", COMMAND_FOCUS, i+1)));
                     }
+
+                    let text = parts.iter().map(|part| part.text.as_str()).collect();
+                    RenderedBlock { name: "players".to_string(), text, icon: None, actions: Vec::new(), parts }
+                },
+                DisplayFormat::Lyrics(_, _) => {
+                    let text = self.lyrics_scroller().map(Scroller::display).unwrap_or_default();
+                    RenderedBlock::text("lyrics", self.escape(text))
+                },
+                DisplayFormat::Art(size) => {
+                    let path = self.art_cache.path(*size)
+                        .map(|path| self.escape(&path.to_string_lossy()))
+                        .unwrap_or_default();
+
+                    RenderedBlock::text("art", path)
+                },
+                DisplayFormat::String(s) => RenderedBlock::text("text", s.clone()),
+                DisplayFormat::Conditional(condition, section) => {
+                    if self.check_condition(condition, status) {
+                        self.render_format(section, status, blocks);
+                    }
+
+                    continue;
+                },
+                DisplayFormat::Clickable(_, _) => unreachable!(),
+            };
+
+            // Custom bindings take precedence over the block's own actions
+            let rendered = match block {
+                DisplayFormat::Clickable(_, bindings) => bindings.iter()
+                    .fold(rendered, |rendered, (button, command)| rendered.with(*button, command)),
+                _ => rendered,
+            };
+
+            blocks.push(rendered);
+        }
+    }
+
+    fn check_condition(&self, condition: &Condition, status: PlaybackStatus) -> bool {
+        match condition {
+            Condition::Playing => status == PlaybackStatus::Playing,
+            Condition::Paused => status == PlaybackStatus::Paused,
+            Condition::Stopped => status == PlaybackStatus::Stopped,
+            Condition::Players(ordering, count) => self.players.len().cmp(count) == *ordering,
+            Condition::Has(tag) => {
+                let meta = match self.current_player().get_metadata() {
+                    Ok(meta) => meta,
+                    Err(_) => return false,
                 };
-            }
+
+                match tag.as_str() {
+                    "art" => meta.art_url().is_some_and(|url| !url.is_empty()),
+                    tag => MetaFormat::from_tag(tag).and_then(|tag| meta_tag(&meta, &tag)).is_some(),
+                }
+            },
+        }
+    }
+
+    /// How far into the current track the player is, from 0 to 1.
+    fn progress(&self) -> Option<f64> {
+        let position = self.current_player().get_position().ok()?;
+        let length = self.current_player().get_metadata().ok()?.length()?;
+
+        if length.is_zero() {
+            return None;
+        }
+
+        Some((position.as_secs_f64() / length.as_secs_f64()).min(1.0))
+    }
+
+    /// Caches the current track's art for the art blocks, if there are any.
+    fn update_art(&mut self, meta: &Metadata) {
+        let sizes: Vec<u8> = all_blocks(self.display_format()).into_iter()
+            .filter_map(|block| match block.block() {
+                DisplayFormat::Art(size) => Some(*size),
+                _ => None,
+            })
+            .collect();
+
+        if !sizes.is_empty() {
+            let track_id = meta.track_id().map(|id| id.as_str().to_string()).unwrap_or_default();
+            self.art_cache.update(&track_id, meta.art_url(), &sizes);
+        }
+    }
+
+    /// Loads the current track's lyrics and scrolls the line being sung, when
+    /// there's a lyrics block or they're part of the JSON state.
+    fn update_lyrics(&mut self, meta: &Metadata) {
+        if self.lyrics_scrollers.is_empty() && self.config.markup_type != MarkupType::Json {
+            return;
+        }
+
+        let track_id = meta.track_id().map(|id| id.as_str().to_string()).unwrap_or_default();
+        let track = (track_id, meta.url().map(str::to_string));
+
+        if self.lyrics_track.as_ref() != Some(&track) {
+            let path = meta.url().and_then(art::file_url_path);
+            let artist = meta.artists().and_then(|a| a.first().copied());
+
+            self.lyrics = lyrics::find(path.as_deref(), self.config.lyrics_dir.as_deref(), artist, meta.title())
+                .and_then(|path| Lyrics::load(&path));
+            self.lyrics_track = Some(track);
+        }
+
+        let format = self.format_idx();
+        let line = self.lyrics_line(0).unwrap_or_default();
+
+        for (_, scroller) in self.lyrics_scrollers.iter_mut().filter(|(f, _)| *f == format) {
+            scroller.set_content(&line);
+            scroller.update();
+        }
+    }
+
+    /// A line of the lyrics relative to the one being sung.
+    fn lyrics_line(&self, relative: usize) -> Option<String> {
+        let lyrics = self.lyrics.as_ref()?;
+        let position = self.current_player().get_position().ok()?;
+
+        let index = match lyrics.index_at(position) {
+            Some(index) => index + relative,
+            None => relative.checked_sub(1)?,
+        };
+
+        lyrics.line(index).map(str::to_string)
+    }
+
+    fn update_meta(&mut self, meta: Metadata) {
+        let mut content = String::new();
+
+        // Optionals render Strings before and after the first valid block
+        fn build_content(content: &mut String, meta: &Metadata, blocks: &[MetaFormat], optional: bool, missing: &str) {
+            let mut flush_buffer = String::new();
+            let mut flush = false;
 
             for block in blocks {
                 match block {
-                    MetaFormat::Artist => flushtag!(first, tags.artists),
-                    MetaFormat::Artists => flushtag!(join, tags.artists),
-                    MetaFormat::Album => flushtag!(tags.album_name),
-                    MetaFormat::AlbumArtist => flushtag!(first, tags.album_artists),
-                    MetaFormat::Title => flushtag!(tags.title),
-                    MetaFormat::Track => flushtag!(number, tags.track),
-                    MetaFormat::String(s) => if optional { flush_buffer.push_str(&s); } else { content.push_str(&s); },
-                    MetaFormat::Optional(o) => build_content(content, tags, &o, true),
+                    MetaFormat::String(s) => if optional { flush_buffer.push_str(s); } else { content.push_str(s); },
+                    MetaFormat::Optional(o) => build_content(content, meta, o, true, missing),
+                    tag => {
+                        let (text, fallback) = match tag {
+                            // An empty default only hides the tag
+                            MetaFormat::Default(tag, default) => (
+                                meta_tag(meta, tag).or_else(|| Some(default.clone()).filter(|d| !d.is_empty())),
+                                default.as_str(),
+                            ),
+                            tag => (meta_tag(meta, tag), missing),
+                        };
+
+                        match (text, optional) {
+                            (Some(text), true) => {
+                                flush = true;
+                                content.push_str(&flush_buffer);
+                                flush_buffer.clear();
+                                content.push_str(&text);
+                            },
+                            (None, true) => if flush {
+                                flush = false;
+                                flush_buffer.clear();
+                            },
+                            (text, false) => content.push_str(text.as_deref().unwrap_or(fallback)),
+                        }
+                    },
                 }
             }
 
-            flushtag!(buffer);
+            if flush {
+                content.push_str(&flush_buffer);
+            }
         }
 
-        build_content(&mut content, tags, &self.config.meta_format, false);
-        let content = content.trim_end();
-        self.meta_scroller.set_content(content);
-        self.meta_scroller.update();
+        let player = self.players.get(self.current_idx);
+        let format = self.config.display_format_idx(player);
+        let bus_name = player.map(|p| p.bus_name().to_string()).unwrap_or_default();
+        let changed = match &self.meta_track {
+            Some((last_bus_name, last_meta)) => *last_bus_name != bus_name || last_meta.as_hashmap() != meta.as_hashmap(),
+            None => true,
+        };
+
+        // Only the current format's blocks are shown
+        for (_, name, scroller) in self.meta_scrollers.iter_mut().filter(|(f, _, _)| *f == format) {
+            if changed {
+                let format = self.config.meta_format(player, name.as_deref());
+
+                content.clear();
+                build_content(&mut content, &meta, format, false, &self.config.missing_text);
+                scroller.set_content(content.trim_end());
+            }
+            scroller.update();
+        }
+
+        if changed {
+            self.meta_track = Some((bus_name, meta));
+        }
     }
 
     fn command(&mut self, command: &str) -> Result<(), DBusError> {
         if self.players.len() == 0 { return Ok(()); }
 
+        let (command, argument) = match command.trim().split_once(' ') {
+            Some((command, argument)) => (command, Some(argument.trim())),
+            None => (command.trim(), None),
+        };
+
         match command {
             COMMAND_PLAY => self.current_player().play()?,
             COMMAND_PAUSE => self.current_player().pause()?,
             COMMAND_STOP => self.current_player().stop()?,
             COMMAND_PREV => self.current_player().previous()?,
             COMMAND_NEXT => self.current_player().next()?,
+            COMMAND_RAISE => {
+                let player = self.current_player();
+                if player.can_raise()? { player.raise()?; }
+            },
+            COMMAND_QUIT => {
+                let player = self.current_player();
+                if player.can_quit()? { player.quit()?; }
+            },
             COMMAND_PLAY_PAUSE => {
                 use PlaybackStatus::*;
 
@@ -477,35 +1030,246 @@ impl PlayerStatus {
                     self.current_idx -= 1;
                 }
             },
+            COMMAND_FOCUS => {
+                if let Some(index) = argument.and_then(|a| self.find_player(a)) {
+                    self.current_idx = index;
+                }
+            },
+            COMMAND_VOLUME => {
+                if let Some(volume) = argument.and_then(|a| a.parse::<u8>().ok()) {
+                    self.set_volume(volume.min(100) as f64 / 100.0)?;
+                }
+            },
+            COMMAND_VOLUME_UP => {
+                let volume = self.current_player().get_volume()?;
+                self.set_volume((volume + VOLUME_STEP).min(1.0))?;
+            },
+            COMMAND_VOLUME_DOWN => {
+                let volume = self.current_player().get_volume()?;
+                self.set_volume((volume - VOLUME_STEP).max(0.0))?;
+            },
+            COMMAND_SEEK => {
+                if let Some(seconds) = argument.and_then(|a| a.parse::<i64>().ok()) {
+                    self.current_player().seek(seconds * 1_000_000)?;
+                }
+            },
+            COMMAND_SEEK_TO => {
+                let meta = self.current_player().get_metadata()?;
+                let position = match (argument.and_then(parse_seek_position), meta.length()) {
+                    (Some(SeekPosition::Time(time)), Some(length)) => Some(time.min(length)),
+                    (Some(SeekPosition::Time(time)), None) => Some(time),
+                    (Some(SeekPosition::Fraction(fraction)), Some(length)) => Some(length.mul_f64(fraction)),
+                    _ => None,
+                };
+
+                if let (Some(position), Some(track_id)) = (position, meta.track_id()) {
+                    self.current_player().set_position(track_id, &position)?;
+                }
+            },
+            COMMAND_TOGGLE_SHUFFLE => {
+                let shuffle = self.current_player().get_shuffle()?;
+                self.current_player().set_shuffle(!shuffle)?;
+            },
+            COMMAND_CYCLE_LOOP => {
+                let status = match self.current_player().get_loop_status()? {
+                    LoopStatus::None => LoopStatus::Playlist,
+                    LoopStatus::Playlist => LoopStatus::Track,
+                    LoopStatus::Track => LoopStatus::None,
+                };
+
+                self.current_player().set_loop_status(status)?;
+            },
+            COMMAND_MUTE => {
+                let bus_name = self.current_player().bus_name().to_string();
+                let volume = self.current_player().get_volume()?;
+
+                if volume > 0.0 {
+                    self.current_player().set_volume(0.0)?;
+                    self.muted_volumes.insert(bus_name, volume);
+                } else if let Some(previous) = self.muted_volumes.remove(&bus_name) {
+                    self.current_player().set_volume(previous)?;
+                }
+            },
             _ => (),
         }
 
         Ok(())
     }
 
-    fn action(&self, command: &str, icon: &str) -> String {
-        let markup_type = if DEBUG_BUILD {
-            MarkupType::Plain
-        } else {
-            self.config.markup_type
-        };
+    /// Finds a player by its position, starting at 1 like in `[info]`, or by
+    /// part of its name or bus name.
+    fn find_player(&self, player: &str) -> Option<usize> {
+        if let Ok(index) = player.parse::<usize>() {
+            return index.checked_sub(1).filter(|i| *i < self.players.len());
+        }
+
+        let player = player.to_lowercase();
+        self.players.iter().position(|p| {
+            p.identity().to_lowercase().contains(&player) || p.bus_name().to_lowercase().contains(&player)
+        })
+    }
+
+    /// Changes the volume, forgetting the one from before muting.
+    fn set_volume(&mut self, volume: f64) -> Result<(), DBusError> {
+        let bus_name = self.current_player().bus_name().to_string();
+        self.muted_volumes.remove(&bus_name);
+        self.current_player().set_volume(volume)
+    }
+
+    /// Handles a click event sent by i3bar/swaybar on stdin.
+    fn click(&mut self, event: &Json) -> Result<(), DBusError> {
+        let button = event.get("button")
+            .and_then(Json::as_f64)
+            .and_then(|b| MouseButton::from_number(b as u8));
+        let block = event.get("instance")
+            .and_then(Json::as_str)
+            .and_then(|i| i.parse::<usize>().ok())
+            .and_then(|i| self.click_blocks.get(i));
+
+        // Blocks with parts are a single i3bar block, so find the clicked part
+        let block = match block {
+            Some(block) if !block.parts.is_empty() => {
+                let x = event.get("relative_x").and_then(Json::as_f64);
+                let width = event.get("width").and_then(Json::as_f64);
+
+                match (x, width) {
+                    // Assuming every character is as wide
+                    (Some(x), Some(width)) if width > 0.0 => {
+                        let total: usize = block.parts.iter().map(|p| str_len!(p.text)).sum();
+                        let clicked = (x / width * total as f64) as usize;
+                        let mut end = 0;
+
+                        block.parts.iter()
+                            .find(|part| {
+                                end += str_len!(part.text);
+                                clicked < end
+                            })
+                            .or(block.parts.last())
+                    },
+                    _ => Some(block),
+                }
+            },
+            block => block,
+        };
+
+        let command = block
+            .and_then(|block| block.actions.iter().find(|(b, _)| Some(*b) == button))
+            .map(|(_, command)| command.clone());
+
+        match command {
+            Some(command) => self.command(&command),
+            None => Ok(()),
+        }
+    }
+
+    /// The markup type used for the blocks. Debug builds always use plain text.
+    fn block_markup(&self) -> MarkupType {
+        self.config.markup_type.for_blocks()
+    }
+
+    /// Escapes content that didn't come from the user, like metadata.
+    fn escape(&self, content: &str) -> String {
+        self.block_markup().escape(content)
+    }
+
+    /// A block's icon, either the one set in the display format or the theme's.
+    fn icon(&self, custom: &Option<String>, default: &str) -> String {
+        custom.as_deref().unwrap_or(default).to_string()
+    }
+
+    /// Applies the markup to a rendered block.
+    fn markup(&self, block: &RenderedBlock) -> String {
+        if !block.parts.is_empty() {
+            block.parts.iter().map(|part| self.markup(part)).collect()
+        } else if block.actions.is_empty() {
+            self.text(&block.text)
+        } else {
+            self.action(&block.actions, &block.text)
+        }
+    }
+
+    fn action(&self, actions: &[(MouseButton, String)], icon: &str) -> String {
+        let markup_type = self.block_markup();
+
+        match markup_type {
+            MarkupType::Polybar => {
+                let mut result = String::new();
+                for (button, command) in actions {
+                    let command = format!("{} {}", self.bin_path.display(), command);
+                    result.push_str(&format!("%{{A{}:{}:}}", *button as u8, escape_action_command(&command)));
+                }
+                result.push_str(icon);
+                result.push_str(&"%{A}".repeat(actions.len()));
+                result
+            },
+            MarkupType::Yuck => {
+                let bin_path = self.bin_path.display();
+                let mut scroll = Vec::new();
+
+                // Buttons can't be scrolled, so they get wrapped in an eventbox
+                let mut result = if actions.iter().any(|(b, _)| matches!(b, MouseButton::ScrollUp | MouseButton::ScrollDown)) {
+                    String::from("(eventbox")
+                } else {
+                    String::from("(button")
+                };
+
+                for (button, command) in actions {
+                    let attribute = match button {
+                        MouseButton::Left => "onclick",
+                        MouseButton::Middle => "onmiddleclick",
+                        MouseButton::Right => "onrightclick",
+                        MouseButton::ScrollUp => { scroll.push(format!("[ {{}} = up ] && {} {}", bin_path, command)); continue; },
+                        MouseButton::ScrollDown => { scroll.push(format!("[ {{}} = down ] && {} {}", bin_path, command)); continue; },
+                    };
+                    result.push_str(&format!(" :{} `{} {}`", attribute, bin_path, command));
+                }
 
-        match markup_type {
-            MarkupType::Polybar => format!("%{{A1:{} {}:}}{}%{{A}}", self.bin_path.display(), command, icon),
-            MarkupType::Yuck => format!(
-                "(button :onclick `{} {}` `{}`)",
-                self.bin_path.display(), command, icon
-            ),
+                // The text is already escaped for Pango, so it's set as markup
+                if scroll.is_empty() {
+                    result.push_str(&format!(" {})", self.text(icon)));
+                } else {
+                    // `{}` is replaced with the direction by eww
+                    result.push_str(&format!(" :onscroll `{}` {})", scroll.join("; "), self.text(icon)));
+                }
+
+                result
+            },
+            // Lemonbar writes the command to stdout, see `--read-commands`
+            MarkupType::Lemonbar => {
+                let mut result = String::new();
+                for (button, command) in actions {
+                    let button = match button {
+                        MouseButton::Left => String::new(),
+                        b => (*b as u8).to_string(),
+                    };
+                    result.push_str(&format!("%{{A{}:{}:}}", button, escape_action_command(command)));
+                }
+                result.push_str(icon);
+                result.push_str(&"%{A}".repeat(actions.len()));
+                result
+            },
+            MarkupType::Xmobar => {
+                let mut result = String::new();
+                for (button, command) in actions {
+                    result.push_str(&format!("<action=`{} {}` button={}>", self.bin_path.display(), command, *button as u8));
+                }
+                result.push_str(icon);
+                result.push_str(&"</action>".repeat(actions.len()));
+                result
+            },
+            // Ranges only have a name, so only the left button is used. The
+            // name is the command, see the README for the bindings.
+            MarkupType::Tmux => match actions.iter().find(|(b, _)| *b == MouseButton::Left).and_then(|(_, c)| tmux_range_name(c)) {
+                Some(name) => format!("#[range=user|{}]{}#[norange]", name, icon),
+                None => icon.to_string(),
+            },
+            MarkupType::Waybar | MarkupType::I3bar | MarkupType::Json | MarkupType::Eww | MarkupType::Yambar |
             MarkupType::Plain => icon.to_string(),
         }
     }
 
     fn text<T: std::fmt::Display>(&self, content: T) -> String {
-        let markup_type = if DEBUG_BUILD {
-            MarkupType::Plain
-        } else {
-            self.config.markup_type
-        };
+        let markup_type = self.block_markup();
 
         let content_string = content.to_string();
 
@@ -534,6 +1298,39 @@ impl PlayerStatus {
     }
 }
 
+impl RenderedBlock {
+    fn action(name: &str, command: &str, icon: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            text: icon.to_string(),
+            icon: Some(icon.to_string()),
+            actions: vec![(MouseButton::Left, command.to_string())],
+            parts: Vec::new(),
+        }
+    }
+
+    fn icon(name: &str, icon: &str) -> Self {
+        Self { name: name.to_string(), text: icon.to_string(), icon: Some(icon.to_string()), actions: Vec::new(), parts: Vec::new() }
+    }
+
+    fn text(name: &str, text: String) -> Self {
+        Self { name: name.to_string(), text, icon: None, actions: Vec::new(), parts: Vec::new() }
+    }
+
+    /// Sets the action of a mouse button, replacing the previous one. Parts
+    /// get the same action.
+    fn with(mut self, button: MouseButton, command: &str) -> Self {
+        self.actions.retain(|(b, _)| *b != button);
+        self.actions.push((button, command.to_string()));
+        self.parts = self.parts.into_iter().map(|part| part.with(button, command)).collect();
+        self
+    }
+
+    fn command(&self, button: MouseButton) -> Option<&str> {
+        self.actions.iter().find(|(b, _)| *b == button).map(|(_, c)| c.as_str())
+    }
+}
+
 impl Scroller {
     pub fn new(size: u8, wait: u8) -> Self {
         Scroller {
@@ -552,8 +1349,13 @@ impl Scroller {
     }
 
     pub fn set_content(&mut self, content: &str) {
+        // Scrollers are a single line, anything else would split the output
+        let content: String = content.chars()
+            .map(|c| if c.is_control() { ' ' } else { c })
+            .collect();
+
         if self.content != content {
-            self.content = content.to_string();
+            self.content = content;
             self.reset_head();
         }
     }
@@ -605,6 +1407,76 @@ impl Scroller {
     pub fn display(&self) -> &str {
         &self.buffer
     }
+
+    /// The displayed text, without polybar's padding.
+    pub fn visible(&self) -> &str {
+        self.buffer.trim_end_matches(EMPTY_CHAR)
+    }
+
+    /// The whole content, without any truncation.
+    pub fn content(&self) -> &str {
+        &self.content
+    }
+}
+
+impl MarkupType {
+    /// The markup type used for the blocks. Debug builds always use plain text.
+    fn for_blocks(self) -> Self {
+        if DEBUG_BUILD {
+            MarkupType::Plain
+        } else {
+            self
+        }
+    }
+
+    /// Escapes text so it's shown as-is by the bar, instead of being read as markup.
+    pub fn escape(&self, content: &str) -> String {
+        fn escape_pango(c: char, escaped: &mut String) {
+            match c {
+                '&' => escaped.push_str("&amp;"),
+                '<' => escaped.push_str("&lt;"),
+                '>' => escaped.push_str("&gt;"),
+                c => escaped.push(c),
+            }
+        }
+
+        let mut escaped = String::with_capacity(content.len());
+
+        match self {
+            // Only the start of a tag needs escaping
+            MarkupType::Polybar => escaped.push_str(&content.replace("%{", "%%{")),
+
+            // Lemonbar has no escaping, so the tag is broken up instead
+            MarkupType::Lemonbar => escaped.push_str(&content.replace("%{", &format!("%{}{{", EMPTY_CHAR))),
+
+            // Pango markup inside a backtick string literal
+            MarkupType::Yuck => for c in content.chars() {
+                match c {
+                    '\\' => escaped.push_str("\\\\"),
+                    '`' => escaped.push_str("\\`"),
+                    c => escape_pango(c, &mut escaped),
+                }
+            },
+
+            // The length is in characters, not bytes
+            MarkupType::Xmobar => if !content.is_empty() {
+                escaped.push_str(&format!("<raw={}:{}/>", str_len!(content), content));
+            },
+
+            MarkupType::Tmux => escaped.push_str(&content.replace('#', "##")),
+
+            MarkupType::Waybar => for c in content.chars() {
+                escape_pango(c, &mut escaped);
+            },
+
+            // Blocks are JSON strings without markup
+            MarkupType::I3bar | MarkupType::Json | MarkupType::Eww | MarkupType::Yambar | MarkupType::Plain => {
+                escaped.push_str(content)
+            },
+        }
+
+        escaped
+    }
 }
 
 impl<'a> From<&'a str> for MarkupType {
@@ -612,16 +1484,110 @@ impl<'a> From<&'a str> for MarkupType {
         match name {
             "polybar" => Self::Polybar,
             "yuck" => Self::Yuck,
+            "waybar" => Self::Waybar,
+            "i3bar" => Self::I3bar,
+            "json" => Self::Json,
+            "eww" => Self::Eww,
+            "lemonbar" => Self::Lemonbar,
+            "xmobar" => Self::Xmobar,
+            "tmux" => Self::Tmux,
+            "yambar" => Self::Yambar,
             "none" => Self::Plain,
             _ => unreachable!(), // possible values are validated by clap
         }
     }
 }
 
-fn parse_cli() -> Result<Either<String, Config>, String> {
+/// Splits a progress bar into cells. `style` is either `smooth`, for eighth
+/// blocks, or the glyphs for filled, head (optional) and empty cells.
+fn progress_cells(progress: f64, width: usize, style: &str) -> Vec<String> {
+    let glyphs: Vec<char> = style.chars().collect();
+
+    if style == "smooth" || glyphs.len() < 2 {
+        let eighths = (progress * width as f64 * 8.0).round() as usize;
+
+        return (0..width).map(|i| match eighths.saturating_sub(i*8).min(8) {
+            0 => " ".to_string(),
+            n => PROGRESS_SMOOTH_GLYPHS[n-1].to_string(),
+        }).collect();
+    }
+
+    let (filled, head, empty) = match glyphs[..] {
+        [filled, head, empty] => (filled, Some(head), empty),
+        [filled, empty, ..] => (filled, None, empty),
+        _ => unreachable!(),
+    };
+
+    let filled_cells = match head {
+        Some(_) => (progress * width as f64).floor() as usize,
+        None => (progress * width as f64).round() as usize,
+    };
+
+    (0..width).map(|i| {
+        if i < filled_cells { filled }
+        else if i == filled_cells { head.unwrap_or(empty) }
+        else { empty }
+    }.to_string()).collect()
+}
+
+/// Where to seek to in the current track.
+#[derive(Debug, PartialEq)]
+enum SeekPosition {
+    Time(Duration),
+    /// From 0 to 1, for percentages.
+    Fraction(f64),
+}
+
+/// Parses a position like `1:23`, `1:02:03` or `50%`.
+fn parse_seek_position(value: &str) -> Option<SeekPosition> {
+    if value.ends_with('%') {
+        return parse_percentage(value).map(SeekPosition::Fraction);
+    }
+
+    let mut seconds = 0u64;
+    let mut fields = 0;
+
+    for field in value.split(':') {
+        let field = field.trim().parse::<u64>().ok()?;
+
+        // Only the first field can go over 59, as in `90:00`
+        if fields > 0 && field >= 60 {
+            return None;
+        }
+
+        seconds = seconds * 60 + field;
+        fields += 1;
+    }
+
+    if (2..=3).contains(&fields) {
+        Some(SeekPosition::Time(Duration::from_secs(seconds)))
+    } else {
+        None
+    }
+}
+
+/// Parses a percentage like `50%` into a fraction from 0 to 1.
+fn parse_percentage(value: &str) -> Option<f64> {
+    let percentage = value.strip_suffix('%')?.trim().parse::<f64>().ok()?;
+    if (0.0..=100.0).contains(&percentage) {
+        Some(percentage / 100.0)
+    } else {
+        None
+    }
+}
+
+fn status_name(status: PlaybackStatus) -> &'static str {
+    match status {
+        PlaybackStatus::Playing => "playing",
+        PlaybackStatus::Paused => "paused",
+        PlaybackStatus::Stopped => "stopped",
+    }
+}
+
+fn parse_cli() -> Result<Mode, String> {
     use clap::{
         builder::PossibleValuesParser,
-        Arg, Command,
+        Arg, ArgAction, Command,
         value_parser,
     };
 
@@ -633,6 +1599,28 @@ fn parse_cli() -> Result<Either<String, Config>, String> {
              .help("Which command to send to the current running instance")
              .value_parser(PossibleValuesParser::new(COMMANDS))
         )
+        .arg(Arg::new("argument")
+             .help("The argument of commands that take one, like the level for volume or the position for seek-to")
+             .requires("command")
+             .allow_negative_numbers(true)
+        )
+        .arg(Arg::new("read-commands")
+             .help("Send every command read from stdin to the current running instance, one per line")
+             .long("read-commands")
+             .action(ArgAction::SetTrue)
+             .conflicts_with("command")
+        )
+        .arg(Arg::new("once")
+             .help("Print the current state a single time and exit, instead of running in tail mode")
+             .long("once")
+             .action(ArgAction::SetTrue)
+             .conflicts_with_all(["command", "read-commands"])
+        )
+        .arg(Arg::new("range")
+             .help("Run the command of a clicked tmux range, like seek-to_40%, directly on the first player if no instance is running")
+             .long("range")
+             .conflicts_with_all(["command", "read-commands", "once"])
+        )
         .arg(Arg::new("display-format")
              .help("How the player presents itself")
              .short('f')
@@ -640,10 +1628,20 @@ fn parse_cli() -> Result<Either<String, Config>, String> {
              .default_value(DEFAULT_DISPLAY_FORMAT)
         )
         .arg(Arg::new("metadata-format")
-             .help("What information about the song will be shown")
+             .help("What information about the song will be shown, or name=format for named metadata blocks")
              .short('m')
              .long("metadata-format")
-             .default_value(DEFAULT_META_FORMAT)
+             .action(ArgAction::Append)
+        )
+        .arg(Arg::new("player-display-format")
+             .help("The display format of the players matching a pattern, as in firefox=[play-pause] [metadata]")
+             .long("player-display-format")
+             .action(ArgAction::Append)
+        )
+        .arg(Arg::new("player-metadata-format")
+             .help("The metadata format of the players matching a pattern, as in mpv=[title]")
+             .long("player-metadata-format")
+             .action(ArgAction::Append)
         )
         .arg(Arg::new("refresh-ticks")
              .help("How many ticks to wait to refresh the player cache.")
@@ -657,80 +1655,492 @@ fn parse_cli() -> Result<Either<String, Config>, String> {
              .short('t')
              .long("markup-type")
              .default_value("polybar")
-             .value_parser(PossibleValuesParser::new(["polybar", "yuck", "none"]))
+             .value_parser(PossibleValuesParser::new(["polybar", "yuck", "waybar", "i3bar", "json", "eww", "lemonbar", "xmobar", "tmux", "yambar", "none"]))
         )
         .arg(
             Arg::new("empty-msg")
             .help("The text to show when no players are available")
             .short('e')
             .long("empty-msg")
-            .default_value(EMPTY_MSG)
+        )
+        .arg(Arg::new("missing-text")
+             .help("The text to show for missing tags and times, unless they have a default")
+             .long("missing-text")
+             .default_value(MISSING_TEXT)
+        )
+        .arg(Arg::new("icons")
+             .help("Which icons to use, for fonts other than Font Awesome")
+             .short('i')
+             .long("icons")
+             .default_value("fontawesome")
+             .value_parser(PossibleValuesParser::new(icons::THEMES))
+        )
+        .arg(Arg::new("icon")
+             .help("Replaces a single icon of the theme, as in play=>")
+             .long("icon")
+             .action(ArgAction::Append)
+        )
+        .arg(Arg::new("lyrics-dir")
+             .help("Where to look for LRC files, besides next to the track")
+             .short('l')
+             .long("lyrics-dir")
+             .value_parser(value_parser!(PathBuf))
         )
     .get_matches();
 
-    if let Some(command) = matches.get_one::<String>("command") {
-        Ok(Either::Left(command.to_owned()))
+    if matches.get_flag("read-commands") {
+        Ok(Mode::ReadCommands)
+    } else if let Some(command) = matches.get_one::<String>("command") {
+        let argument = matches.get_one::<String>("argument");
+        check_command_argument(command, argument.map(|a| a.as_str()))?;
+
+        let command = match argument {
+            Some(argument) => format!("{} {}", command, argument),
+            None => command.to_owned(),
+        };
+
+        Ok(Mode::Command(command))
     } else {
         let display_format = matches
             .get_one::<String>("display-format")
             .expect("has default value");
 
-        let meta_format = matches
-            .get_one::<String>("metadata-format")
-            .expect("has default value");
-
         let display_format = match process_display_format(display_format) {
             Ok(v) => v,
             Err(e) => return Err(format!("Display format - {}", e)),
         };
 
-        let meta_format = match process_meta_format(meta_format) {
-            Ok(v) => v,
-            Err(e) => return Err(format!("Metadata format - {}", e)),
+        let mut meta_format = None;
+        let mut named_meta_formats = HashMap::new();
+
+        for format in matches.get_many::<String>("metadata-format").into_iter().flatten() {
+            let (name, format) = match split_meta_format_name(format) {
+                Some((name, format)) => (Some(name), format),
+                None => (None, format.as_str()),
+            };
+
+            let format = match process_meta_format(format) {
+                Ok(v) => v,
+                Err(e) => match name {
+                    Some(name) => return Err(format!("Metadata format '{}' - {}", name, e)),
+                    None => return Err(format!("Metadata format - {}", e)),
+                },
+            };
+
+            match name {
+                Some(name) => { named_meta_formats.insert(name.to_string(), format); },
+                None => meta_format = Some(format),
+            }
+        }
+
+        let meta_format = match meta_format {
+            Some(format) => format,
+            None => process_meta_format(DEFAULT_META_FORMAT).expect("default format is valid"),
         };
 
-        let mut metadata_test = false;
-        for fmt in &display_format {
-            if let DisplayFormat::Metadata(_, _) = fmt {
-                metadata_test = true;
-                break;
+        let mut player_formats = Vec::<PlayerFormat>::new();
+
+        fn format_entry<'a>(formats: &'a mut Vec<PlayerFormat>, pattern: &str) -> &'a mut PlayerFormat {
+            let pattern = Pattern::from_full_glob(&pattern.to_lowercase());
+
+            match formats.iter().position(|format| format.pattern == pattern) {
+                Some(i) => &mut formats[i],
+                None => {
+                    formats.push(PlayerFormat { pattern, display_format: None, meta_format: None });
+                    formats.last_mut().unwrap()
+                },
+            }
+        }
+
+        for format in matches.get_many::<String>("player-display-format").into_iter().flatten() {
+            let (pattern, format) = match split_player_format(format) {
+                Some(v) => v,
+                None => return Err(format!("Invalid player display format '{}', expected pattern=format", format)),
+            };
+
+            match process_display_format(format) {
+                Ok(format) => format_entry(&mut player_formats, pattern).display_format = Some(format),
+                Err(e) => return Err(format!("Display format for '{}' - {}", pattern, e)),
+            }
+        }
+
+        for format in matches.get_many::<String>("player-metadata-format").into_iter().flatten() {
+            let (pattern, format) = match split_player_format(format) {
+                Some(v) => v,
+                None => return Err(format!("Invalid player metadata format '{}', expected pattern=format", format)),
+            };
+
+            match process_meta_format(format) {
+                Ok(format) => format_entry(&mut player_formats, pattern).meta_format = Some(format),
+                Err(e) => return Err(format!("Metadata format for '{}' - {}", pattern, e)),
             }
         }
 
-        if !metadata_test {
-            return Err("Display format has no metadata block.".to_string());
+        // A format like `np=[title]` is taken as a named one, so it shouldn't
+        // be silently ignored when no block has that name
+        let meta_names: Vec<&str> = player_formats.iter()
+            .filter_map(|format| format.display_format.as_deref())
+            .chain([display_format.as_slice()])
+            .flat_map(meta_block_names)
+            .collect();
+
+        if let Some(name) = named_meta_formats.keys().find(|name| !meta_names.contains(&name.as_str())) {
+            return Err(format!("Metadata format '{}' is named, but no display format has a [metadata#{}] block", name, name));
+        }
+
+        let markup_type: MarkupType = matches
+            .get_one::<String>("markup-type")
+            .expect("has default-value")
+            .as_str()
+            .into();
+
+        let mut icons = IconTheme::from_name(matches.get_one::<String>("icons").expect("has default value"))
+            .expect("checked by clap");
+
+        // Themes like ascii have characters that need escaping, while icons
+        // given with `--icon` are written for the markup like the format is
+        icons.map_icons(|icon| markup_type.for_blocks().escape(icon));
+
+        for icon in matches.get_many::<String>("icon").into_iter().flatten() {
+            let valid = match icon.split_once('=') {
+                Some((name, value)) => icons.set(name.trim(), value),
+                None => false,
+            };
+
+            if !valid {
+                return Err(format!("Invalid icon '{}', expected name=icon with a name out of {}", icon, icons::ICONS.join(", ")));
+            }
         }
 
-        Ok(Either::Right(Config {
+        let empty_msg = match matches.get_one::<String>("empty-msg") {
+            Some(msg) => msg.to_owned(),
+            None => format!("{} {}", icons.empty, EMPTY_MSG),
+        };
+
+        let config = Config {
             display_format,
             meta_format,
+            named_meta_formats,
+            player_formats,
             refresh_wait: *matches
                 .get_one::<u8>("refresh-ticks")
                 .expect("has_default value"),
-            markup_type: matches
-                .get_one::<String>("markup-type")
-                .expect("has default-value")
-                .as_str()
-                .into(),
-            empty_msg: matches
-                .get_one::<String>("empty-msg")
+            markup_type,
+            empty_msg,
+            missing_text: matches
+                .get_one::<String>("missing-text")
                 .expect("has default value")
-                .to_owned()
-        }))
+                .to_owned(),
+            lyrics_dir: matches.get_one::<PathBuf>("lyrics-dir").cloned(),
+            icons,
+        };
+
+        if let Some(name) = matches.get_one::<String>("range") {
+            let (command, argument) = tmux_range_command(name)
+                .ok_or_else(|| format!("Invalid range '{}', expected a command like next or seek-to_40%", name))?;
+            check_command_argument(command, argument)?;
+
+            let command = match argument {
+                Some(argument) => format!("{} {}", command, argument),
+                None => command.to_owned(),
+            };
+
+            Ok(Mode::Range(command, config))
+        } else if matches.get_flag("once") {
+            Ok(Mode::Once(config))
+        } else {
+            Ok(Mode::Controller(config))
+        }
+    }
+}
+
+/// The text of a metadata format tag, if the track has it.
+fn meta_tag(meta: &Metadata, tag: &MetaFormat) -> Option<String> {
+    let list = |key: &str| meta.get(key).and_then(metadata_text);
+    let first = |list: Option<Vec<&str>>| list?.first().filter(|s| !s.is_empty()).map(|s| s.to_string());
+
+    let text = match tag {
+        MetaFormat::Artist => first(meta.artists()),
+        MetaFormat::Artists => list("xesam:artist"),
+        MetaFormat::Album => meta.album_name().map(str::to_string),
+        MetaFormat::AlbumArtist => first(meta.album_artists()),
+        MetaFormat::Title => meta.title().map(str::to_string),
+        MetaFormat::Track => meta.track_number().map(|n| n.to_string()),
+        MetaFormat::Genre => list("xesam:genre"),
+        MetaFormat::Disc => meta.disc_number().map(|n| n.to_string()),
+        MetaFormat::Date => list("xesam:contentCreated"),
+        MetaFormat::Year => list("xesam:contentCreated")
+            .and_then(|date| date.get(..4).filter(|y| y.chars().all(|c| c.is_ascii_digit())).map(str::to_string)),
+        MetaFormat::Composer => list("xesam:composer"),
+        MetaFormat::Lyricist => list("xesam:lyricist"),
+        MetaFormat::Comment => list("xesam:comment"),
+        MetaFormat::Rating => meta.get("xesam:userRating").and_then(|v| v.as_f64()).map(rating_stars),
+        MetaFormat::Length => meta.length().map(format_length),
+        MetaFormat::Url => meta.url().map(str::to_string),
+        MetaFormat::TrackId => meta.track_id().map(|id| id.as_str().to_string()),
+        MetaFormat::Key(key) => list(key),
+        MetaFormat::Filtered(tag, filters) => meta_tag(meta, tag).map(|text| apply_filters(&text, filters)),
+        MetaFormat::Default(tag, _) => meta_tag(meta, tag),
+        MetaFormat::Optional(_) | MetaFormat::String(_) => None,
+    };
+
+    text.filter(|t| !t.is_empty())
+}
+
+/// Any metadata value as text, lists being joined by commas.
+fn metadata_text(value: &MetadataValue) -> Option<String> {
+    use MetadataValue::*;
+
+    let text = match value {
+        String(s) => s.clone(),
+        I16(n) => n.to_string(),
+        I32(n) => n.to_string(),
+        I64(n) => n.to_string(),
+        U8(n) => n.to_string(),
+        U16(n) => n.to_string(),
+        U32(n) => n.to_string(),
+        U64(n) => n.to_string(),
+        F64(n) => n.to_string(),
+        Bool(b) => b.to_string(),
+        Array(values) => values.iter().filter_map(metadata_text).collect::<Vec<_>>().join(", "),
+        Map(_) | Unsupported => return None,
+    };
+
+    if text.is_empty() { None } else { Some(text) }
+}
+
+/// A rating from 0 to 1 as five stars.
+fn rating_stars(rating: f64) -> String {
+    let stars = (rating.clamp(0.0, 1.0) * 5.0).round() as usize;
+    format!("{}{}", "★".repeat(stars), "☆".repeat(5 - stars))
+}
+
+/// A track length like `3:07`, or `1:02:03` when it's over an hour.
+fn format_length(length: Duration) -> String {
+    let seconds = length.as_secs();
+
+    if seconds >= 3600 {
+        format!("{}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60)
+    } else {
+        format!("{}:{:02}", seconds / 60, seconds % 60)
     }
 }
 
+/// Splits a named metadata format like `title=[title]` into its name and format.
+fn split_meta_format_name(format: &str) -> Option<(&str, &str)> {
+    let (name, format) = format.split_once('=')?;
+    let valid_name = name.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '-');
+
+    if !name.is_empty() && valid_name {
+        Some((name, format))
+    } else {
+        None
+    }
+}
+
+/// The names of the named metadata blocks in a display format.
+fn meta_block_names(format: &[DisplayFormat]) -> Vec<&str> {
+    all_blocks(format).into_iter()
+        .filter_map(|block| match block.block() {
+            DisplayFormat::Metadata(_, _, Some(name)) => Some(name.as_str()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Splits a player format like `firefox=[title]` into its pattern and format.
+fn split_player_format(format: &str) -> Option<(&str, &str)> {
+    let (pattern, format) = format.split_once('=')?;
+    let pattern = pattern.trim();
+    let valid_pattern = !pattern.contains(|c: char| c.is_whitespace() || "[]{}<>".contains(c));
+
+    if !pattern.is_empty() && valid_pattern {
+        Some((pattern, format))
+    } else {
+        None
+    }
+}
+
+fn check_command_argument(command: &str, argument: Option<&str>) -> Result<(), String> {
+    match (command, argument) {
+        (COMMAND_VOLUME, Some(a)) => match a.parse::<u8>() {
+            Ok(v) if v <= 100 => Ok(()),
+            _ => Err(format!("Invalid volume '{}', expected a number from 0 to 100", a)),
+        },
+        (COMMAND_FOCUS, None) => Err("The focus command needs a player's position or name".to_string()),
+        (COMMAND_FOCUS, Some(_)) => Ok(()),
+        (COMMAND_VOLUME, None) => Err("The volume command needs a level from 0 to 100".to_string()),
+        (COMMAND_SEEK, Some(a)) => match a.parse::<i64>() {
+            Ok(_) => Ok(()),
+            Err(_) => Err(format!("Invalid offset '{}', expected seconds like +10 or -30", a)),
+        },
+        (COMMAND_SEEK, None) => Err("The seek command needs an offset in seconds like +10 or -30".to_string()),
+        (COMMAND_SEEK_TO, Some(a)) => match parse_seek_position(a) {
+            Some(_) => Ok(()),
+            None => Err(format!("Invalid position '{}', expected a time like 1:23 or a percentage like 50%", a)),
+        },
+        (COMMAND_SEEK_TO, None) => Err("The seek-to command needs a position like 1:23 or 50%".to_string()),
+        (_, Some(a)) => Err(format!("The {} command takes no arguments, got '{}'", command, a)),
+        (_, None) => Ok(()),
+    }
+}
+
+/// The state for the eww markup type, with the commands of the blocks run
+/// through `bin_path`. `players` has the identity, bus name and status of
+/// every player.
+fn eww_state(
+    bin_path: &Path,
+    status: Option<PlaybackStatus>,
+    blocks: &[RenderedBlock],
+    players: &[(&str, &str, Option<PlaybackStatus>)],
+    current_idx: usize,
+    empty_msg: &str,
+) -> Json {
+    let status = status.map(status_name);
+    let command = |command: &str| format!("{} {}", bin_path.display(), command);
+
+    let blocks: Vec<Json> = blocks.iter().map(|block| Json::object()
+        .with("name", block.name.as_str())
+        .with("text", block.text.as_str())
+        .with("icon", block.icon.as_deref())
+        .with("command", block.command(MouseButton::Left).map(command))
+        .with("actions", Json::Object(block.actions.iter()
+            .map(|(b, c)| (b.name().to_string(), command(c).into()))
+            .collect()
+        ))
+        .with("parts", block.parts.iter().map(|part| Json::object()
+            .with("text", part.text.as_str())
+            .with("command", part.command(MouseButton::Left).map(command))
+        ).collect::<Vec<Json>>())
+        .with("status", status)
+    ).collect();
+
+    let players: Vec<Json> = players.iter().enumerate().map(|(i, (identity, bus_name, status))| Json::object()
+        .with("index", i+1)
+        .with("identity", *identity)
+        .with("bus_name", *bus_name)
+        .with("status", status.map(status_name))
+        .with("focused", i == current_idx)
+    ).collect();
+
+    Json::object()
+        .with("status", status)
+        .with("blocks", blocks)
+        .with("players", players)
+        .with("empty_msg", empty_msg)
+}
+
+/// A line of yambar's script protocol, as in `title|string|Song`.
+fn yambar_tag<T: std::fmt::Display>(name: &str, kind: &str, value: T) -> String {
+    // Values can't span multiple lines
+    let value = value.to_string().replace(|c: char| c.is_control(), " ");
+    format!("{}|{}|{}\n", name, kind, value)
+}
+
+/// Progress as a whole percentage, for yambar's `range:0-100` tags.
+fn yambar_percent(progress: Option<f64>) -> u8 {
+    progress.map(|p| (p.clamp(0.0, 1.0) * 100.0).round() as u8).unwrap_or(0)
+}
+
+/// The tags of the display blocks, named `block-` and the block's name so they
+/// can't shadow the other tags, like `status`. Repeated blocks are numbered
+/// from the second one on, as in `block-prev-2`.
+fn yambar_block_tags(blocks: &[RenderedBlock]) -> String {
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    let mut tags = String::new();
+
+    // Literal strings aren't worth exporting
+    for block in blocks.iter().filter(|b| b.name != "text") {
+        let count = counts.entry(&block.name).or_insert(0);
+        *count += 1;
+
+        let name = match *count {
+            1 => format!("block-{}", block.name),
+            n => format!("block-{}-{}", block.name, n),
+        };
+        tags.push_str(&yambar_tag(&name, "string", block.text.trim_end_matches(EMPTY_CHAR)));
+    }
+
+    tags
+}
+
+/// Escapes the colons in a polybar or lemonbar action command, which would
+/// otherwise end it early (e.g. in `seek-to 1:23`).
+fn escape_action_command(command: &str) -> String {
+    command.replace(':', "\\:")
+}
+
+/// A command as the name of a tmux user range, which can't have spaces and is
+/// at most 15 bytes long, so the argument is joined with an underscore (e.g.
+/// `seek-to_40%`). `None` if the command doesn't fit in one.
+fn tmux_range_name(command: &str) -> Option<String> {
+    let name = command.trim().replacen(' ', "_", 1);
+    let valid = name.chars().all(|c| c.is_ascii_alphanumeric() || "-_.:%+".contains(c));
+
+    Some(name).filter(|name| valid && name.len() <= 15)
+}
+
+/// The command and argument a tmux range name stands for.
+fn tmux_range_command(name: &str) -> Option<(&str, Option<&str>)> {
+    let (command, argument) = match name.split_once('_') {
+        Some((command, argument)) => (command, Some(argument)),
+        None => (name, None),
+    };
+
+    Some((command, argument)).filter(|(command, _)| COMMANDS.contains(command))
+}
+
+/// Whether the command is known and has a valid argument, if any.
+fn is_command(command: &str) -> bool {
+    let (name, argument) = match command.trim().split_once(' ') {
+        Some((name, argument)) => (name, Some(argument.trim())),
+        None => (command.trim(), None),
+    };
+
+    COMMANDS.contains(&name) && check_command_argument(name, argument).is_ok()
+}
+
 fn send_command(command: String) -> Result<(), String> {
     let mut pipe = unix_named_pipe::open_write(PIPE_PATH).map_err(|_| "Unable to write to named pipe")?;
     pipe.write_all(command.as_bytes()).map_err(|_| "Couldn't write to pipe")?;
     Ok(())
 }
 
-fn get_command<'a>(pipe: &mut Either<(), File>, buffer: &'a mut String) -> Result<Option<&'a str>, String> {
+/// Runs a command on the first player, for when there's no running instance
+/// to send it to, e.g. when rendering with `--once` and clicking a range.
+fn run_command(command: String, config: Config) -> Result<(), String> {
+    let mut status = PlayerStatus::new(config);
+    status.refresh_cache();
+    status.command(&command).map_err(|e| format!("Command error: {}", e))
+}
+
+fn render_once(config: Config) -> Result<(), String> {
+    let mut status = PlayerStatus::new(config);
+    status.art_cache.set_background(false);
+    status.update();
+    Ok(())
+}
+
+fn read_commands() -> Result<(), String> {
+    for line in stdin().lock().lines() {
+        let line = line.map_err(|_| "Unable to read stdin")?;
+        let command = line.trim();
+
+        // Bars might write other things as well
+        if is_command(command) {
+            send_command(command.to_string())?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads a command from the named pipe, or from the terminal when there's none.
+fn get_command<'a>(pipe: &mut Option<File>, buffer: &'a mut String) -> Result<Option<&'a str>, String> {
     buffer.clear();
 
     match pipe {
-        Either::Left(_) => {
+        None => {
             use crossterm::event::{
                 Event, KeyCode, KeyEvent, KeyModifiers
             };
@@ -785,17 +2195,17 @@ fn get_command<'a>(pipe: &mut Either<(), File>, buffer: &'a mut String) -> Resul
                         code: KeyCode::Char('c'),
                         modifiers: KeyModifiers::CONTROL,
                         ..
-                    }) => return Ok(Some("quit")),
+                    }) => return Ok(Some("exit")),
 
                     _ => (),
                 }
             }
         }
 
-        Either::Right(pipe) => {
+        Some(pipe) => {
             pipe.read_to_string(buffer).map_err(|_| "Unable to read named pipe")?;
-            if buffer.len() > 0 && COMMANDS.contains(&buffer.as_str()) {
-                return Ok(Some(buffer.as_str()));
+            if is_command(buffer) {
+                return Ok(Some(buffer.trim()));
             }
         }
     }
@@ -803,6 +2213,23 @@ fn get_command<'a>(pipe: &mut Either<(), File>, buffer: &'a mut String) -> Resul
     Ok(None)
 }
 
+/// Reads i3bar click events from stdin on a separate thread, since reading
+/// it would block the main loop.
+fn read_click_events() -> Receiver<String> {
+    let (sender, receiver) = mpsc::channel();
+
+    thread::spawn(move || {
+        for line in stdin().lock().lines() {
+            match line {
+                Ok(line) => if sender.send(line).is_err() { break; },
+                Err(_) => break,
+            }
+        }
+    });
+
+    receiver
+}
+
 fn run_controller(config: Config) -> Result<(), String> {
     let term = Arc::new(AtomicBool::new(false));
 
@@ -826,6 +2253,14 @@ fn run_controller(config: Config) -> Result<(), String> {
     crossterm::terminal::enable_raw_mode()
         .map_err(|_| "couldn't enable raw mode for input")?;
 
+    let click_events = if config.markup_type == MarkupType::I3bar {
+        println!("{}", Json::object().with("version", 1u8).with("click_events", true));
+        println!("[");
+        Some(read_click_events())
+    } else {
+        None
+    };
+
     let mut status = PlayerStatus::new(config);
     let mut command_buffer = String::new();
 
@@ -837,7 +2272,7 @@ fn run_controller(config: Config) -> Result<(), String> {
         execute!(stdout(), DisableMouseCapture)
             .map_err(|_| "couldn't disable mouse capture")?;
 
-        Either::Left(())
+        None
     };
 
     #[cfg(not(debug_assertions))]
@@ -851,13 +2286,13 @@ fn run_controller(config: Config) -> Result<(), String> {
         }
 
         unix_named_pipe::create(PIPE_PATH, Some(0o600)).map_err(|_| "Couldn't create named pipe")?;
-        Either::Right(unix_named_pipe::open_read(PIPE_PATH).map_err(|_| "Unable to open named pipe")?)
+        Some(unix_named_pipe::open_read(PIPE_PATH).map_err(|_| "Unable to open named pipe")?)
     };
 
     while !term.load(Ordering::Relaxed) {
         if let Some(cmd) = get_command(&mut command_pipe, &mut command_buffer)? {
             #[cfg(debug_assertions)] {
-                if cmd == "quit" { break; }
+                if cmd == "exit" { break; }
             }
 
             match status.command(cmd) {
@@ -866,6 +2301,20 @@ fn run_controller(config: Config) -> Result<(), String> {
             }
         }
 
+        if let Some(click_events) = &click_events {
+            while let Ok(line) = click_events.try_recv() {
+                // Events are elements of an endless array, one per line
+                let event = line.trim().trim_start_matches(['[', ',']).trim();
+                if event.is_empty() { continue; }
+
+                match Json::parse(event).map(|e| status.click(&e)) {
+                    Ok(Ok(_)) => (),
+                    Ok(Err(e)) => eprintln!("Command error: {}", e),
+                    Err(e) => eprintln!("Click event error: {}", e),
+                }
+            }
+        }
+
         status.update();
         thread::sleep(Duration::from_millis(300));
     }
@@ -883,8 +2332,15 @@ fn run_controller(config: Config) -> Result<(), String> {
 
 fn main() {
     match parse_cli().and_then(|r| match r {
-        Either::Left(command) => send_command(command),
-        Either::Right(config) => run_controller(config),
+        Mode::Command(command) => send_command(command),
+        Mode::Range(command, config) => if Path::new(PIPE_PATH).exists() {
+            send_command(command)
+        } else {
+            run_command(command, config)
+        },
+        Mode::Once(config) => render_once(config),
+        Mode::ReadCommands => read_commands(),
+        Mode::Controller(config) => run_controller(config),
     }) {
         Ok(_) => (),
 
@@ -894,3 +2350,187 @@ fn main() {
         }
     }
 }
+
+#[test]
+fn test_markup_escape() {
+    let title = "Rock & Roll <Live> `100%{x}` \\o/";
+
+    assert_eq!(MarkupType::Polybar.escape(title), "Rock & Roll <Live> `100%%{x}` \\o/");
+    assert_eq!(MarkupType::Yuck.escape(title), "Rock &amp; Roll &lt;Live&gt; \\`100%{x}\\` \\\\o/");
+    assert_eq!(MarkupType::Waybar.escape(title), "Rock &amp; Roll &lt;Live&gt; `100%{x}` \\o/");
+    assert_eq!(MarkupType::Lemonbar.escape(title), "Rock & Roll <Live> `100%\u{feff}{x}` \\o/");
+    assert_eq!(MarkupType::Xmobar.escape("<é>"), "<raw=3:<é>/>");
+    assert_eq!(MarkupType::Xmobar.escape(""), "");
+    assert_eq!(MarkupType::Tmux.escape("#1 #[fg=red]"), "##1 ##[fg=red]");
+    assert_eq!(MarkupType::Plain.escape(title), title);
+}
+
+#[test]
+fn test_yambar_tags() {
+    assert_eq!(yambar_tag("title", "string", "Line\nbreak\tand tab"), "title|string|Line break and tab\n");
+    assert_eq!(yambar_tag("track", "int", 3), "track|int|3\n");
+    assert_eq!(yambar_tag("progress", "range:0-100", yambar_percent(Some(0.424))), "progress|range:0-100|42\n");
+    assert_eq!(yambar_percent(Some(1.2)), 100);
+    assert_eq!(yambar_percent(None), 0);
+
+    let blocks = [
+        RenderedBlock::action("prev", COMMAND_PREV, "<"),
+        RenderedBlock::text("text", " ".to_string()),
+        RenderedBlock::icon("status", "|>"),
+        RenderedBlock::text("metadata", format!("Song{}", EMPTY_CHAR)),
+        RenderedBlock::action("prev", COMMAND_PREV, "<<"),
+    ];
+    assert_eq!(
+        yambar_block_tags(&blocks),
+        "block-prev|string|<\nblock-status|string||>\nblock-metadata|string|Song\nblock-prev-2|string|<<\n",
+    );
+}
+
+#[test]
+fn test_eww_state() {
+    let progress = RenderedBlock {
+        name: "progress".to_string(),
+        text: "#-".to_string(),
+        icon: None,
+        actions: Vec::new(),
+        parts: vec![
+            RenderedBlock::action("progress", "seek-to 0%", "#"),
+            RenderedBlock::text("progress", "-".to_string()),
+        ],
+    };
+    let blocks = [
+        RenderedBlock::action("prev", COMMAND_PREV, "<").with(MouseButton::ScrollUp, COMMAND_VOLUME_UP),
+        RenderedBlock::text("metadata", "Artist & Song".to_string()),
+        progress,
+    ];
+    let players = [
+        ("mpv", "org.mpris.MediaPlayer2.mpv", Some(PlaybackStatus::Paused)),
+        ("Spotify", "org.mpris.MediaPlayer2.spotify", None),
+    ];
+
+    let state = eww_state(Path::new("/bin/cornetroll"), Some(PlaybackStatus::Playing), &blocks, &players, 1, "none");
+    assert_eq!(state.to_string(), concat!(
+        r#"{"status":"playing","blocks":["#,
+        r#"{"name":"prev","text":"<","icon":"<","command":"/bin/cornetroll prev","#,
+        r#""actions":{"left":"/bin/cornetroll prev","scroll-up":"/bin/cornetroll volume-up"},"parts":[],"status":"playing"},"#,
+        r#"{"name":"metadata","text":"Artist & Song","icon":null,"command":null,"actions":{},"parts":[],"status":"playing"},"#,
+        r##"{"name":"progress","text":"#-","icon":null,"command":null,"actions":{},"##,
+        r##""parts":[{"text":"#","command":"/bin/cornetroll seek-to 0%"},{"text":"-","command":null}],"status":"playing"}],"##,
+        r#""players":["#,
+        r#"{"index":1,"identity":"mpv","bus_name":"org.mpris.MediaPlayer2.mpv","status":"paused","focused":false},"#,
+        r#"{"index":2,"identity":"Spotify","bus_name":"org.mpris.MediaPlayer2.spotify","status":null,"focused":true}],"#,
+        r#""empty_msg":"none"}"#,
+    ));
+
+    let empty = eww_state(Path::new("/bin/cornetroll"), None, &[], &[], 0, "none");
+    assert_eq!(empty.to_string(), r#"{"status":null,"blocks":[],"players":[],"empty_msg":"none"}"#);
+}
+
+#[test]
+fn test_progress_cells() {
+    assert_eq!(progress_cells(0.5, 4, "smooth").concat(), "██  ");
+    assert_eq!(progress_cells(0.75, 2, "smooth").concat(), "█▌");
+    assert_eq!(progress_cells(0.0, 3, "smooth").concat(), "   ");
+    assert_eq!(progress_cells(0.5, 4, "#-").concat(), "##--");
+    assert_eq!(progress_cells(0.5, 5, "=>-").concat(), "==>--");
+    assert_eq!(progress_cells(1.0, 3, "=>-").concat(), "===");
+    assert_eq!(parse_percentage("50%"), Some(0.5));
+    assert_eq!(parse_percentage("150%"), None);
+    assert_eq!(parse_percentage("50"), None);
+}
+
+#[test]
+fn test_seek_arguments() {
+    assert_eq!(parse_seek_position("1:23"), Some(SeekPosition::Time(Duration::from_secs(83))));
+    assert_eq!(parse_seek_position("1:02:03"), Some(SeekPosition::Time(Duration::from_secs(3723))));
+    assert_eq!(parse_seek_position("90:00"), Some(SeekPosition::Time(Duration::from_secs(5400))));
+    assert_eq!(parse_seek_position("25%"), Some(SeekPosition::Fraction(0.25)));
+    assert_eq!(parse_seek_position("1:60"), None);
+    assert_eq!(parse_seek_position("83"), None);
+    assert_eq!(parse_seek_position("1:2:3:4"), None);
+
+    assert!(is_command("seek +10"));
+    assert!(is_command("seek -30"));
+    assert!(is_command("seek-to 1:23"));
+    assert!(is_command("play-pause\n"));
+    assert!(!is_command("seek"));
+    assert!(!is_command("seek forward"));
+    assert!(!is_command("next 2"));
+}
+
+#[test]
+fn test_meta_tags() {
+    let meta = Metadata::from(HashMap::from([
+        ("mpris:trackid".to_string(), MetadataValue::String("/track/1".to_string())),
+        ("mpris:length".to_string(), MetadataValue::I64(187_000_000)),
+        ("xesam:composer".to_string(), MetadataValue::Array(vec![
+            MetadataValue::String("Bach".to_string()),
+            MetadataValue::String(String::new()),
+            MetadataValue::String("Busoni".to_string()),
+        ])),
+        ("xesam:contentCreated".to_string(), MetadataValue::String("1998-05-12".to_string())),
+        ("xesam:userRating".to_string(), MetadataValue::F64(0.8)),
+        ("xesam:audioBPM".to_string(), MetadataValue::I32(128)),
+    ]));
+
+    assert_eq!(meta_tag(&meta, &MetaFormat::TrackId).as_deref(), Some("/track/1"));
+    assert_eq!(meta_tag(&meta, &MetaFormat::Length).as_deref(), Some("3:07"));
+    assert_eq!(meta_tag(&meta, &MetaFormat::Composer).as_deref(), Some("Bach, Busoni"));
+    assert_eq!(meta_tag(&meta, &MetaFormat::Year).as_deref(), Some("1998"));
+    assert_eq!(meta_tag(&meta, &MetaFormat::Rating).as_deref(), Some("★★★★☆"));
+    assert_eq!(meta_tag(&meta, &MetaFormat::Key("xesam:audioBPM".to_string())).as_deref(), Some("128"));
+    assert_eq!(meta_tag(&meta, &MetaFormat::Genre), None);
+    assert_eq!(format_length(Duration::from_secs(3723)), "1:02:03");
+}
+
+#[test]
+fn test_player_formats() {
+    assert_eq!(split_player_format("firefox=[title]"), Some(("firefox", "[title]")));
+    assert_eq!(split_player_format("*.mpv=[title;left=play-pause]"), Some(("*.mpv", "[title;left=play-pause]")));
+    assert_eq!(split_player_format("[info;left=next]"), None);
+    assert_eq!(split_player_format("=[title]"), None);
+
+    let format = PlayerFormat {
+        pattern: Pattern::from_full_glob("spotify"),
+        display_format: None,
+        meta_format: None,
+    };
+    assert!(format.matches("Spotify", "org.mpris.MediaPlayer2.spotify"));
+    assert!(!format.matches("mpv", "org.mpris.MediaPlayer2.mpv"));
+    assert!(PlayerFormat { pattern: Pattern::from_full_glob("*.firefox.*"), ..format }
+        .matches("Mozilla Firefox", "org.mpris.MediaPlayer2.firefox.instance_1_42"));
+}
+
+#[test]
+fn test_meta_format_names() {
+    assert_eq!(split_meta_format_name("title=[title]"), Some(("title", "[title]")));
+    assert_eq!(split_meta_format_name("<[artist] - >[title]"), None);
+    assert_eq!(split_meta_format_name("[title] = [album]"), None);
+    assert_eq!(split_meta_format_name("=[title]"), None);
+
+    let format = process_display_format("[metadata] {playing:[metadata#np]} [metadata#title:20]").unwrap();
+    assert_eq!(meta_block_names(&format), ["np", "title"]);
+}
+
+#[test]
+fn test_action_escape() {
+    assert_eq!(escape_action_command("/usr/bin/cornetroll next"), "/usr/bin/cornetroll next");
+    assert_eq!(escape_action_command("/usr/bin/cornetroll seek-to 1:23"), "/usr/bin/cornetroll seek-to 1\\:23");
+    assert_eq!(escape_action_command("seek-to 1:02:03"), "seek-to 1\\:02\\:03");
+}
+
+#[test]
+fn test_tmux_ranges() {
+    assert_eq!(tmux_range_name("next").as_deref(), Some("next"));
+    assert_eq!(tmux_range_name("seek-to 40%").as_deref(), Some("seek-to_40%"));
+    assert_eq!(tmux_range_name("seek -10").as_deref(), Some("seek_-10"));
+    assert_eq!(tmux_range_name("focus my player"), None);
+    assert_eq!(tmux_range_name("focus a,b"), None);
+    assert_eq!(tmux_range_name("focus spotify-beta"), None);
+
+    assert_eq!(tmux_range_command("seek-to_40%"), Some(("seek-to", Some("40%"))));
+    assert_eq!(tmux_range_command("focus_my_player"), Some(("focus", Some("my_player"))));
+    assert_eq!(tmux_range_command("next"), Some(("next", None)));
+    assert_eq!(tmux_range_command("[title]_x"), None);
+    assert_eq!(tmux_range_command("window"), None);
+}
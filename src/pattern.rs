@@ -0,0 +1,88 @@
+use regex::Regex;
+
+/// A compiled pattern for the metadata filters and player formats. Patterns
+/// are compared by their source, so the formats holding them can be too.
+#[derive(Clone, Debug)]
+pub struct Pattern(Regex);
+
+impl Pattern {
+    pub fn new(pattern: &str) -> Result<Self, regex::Error> {
+        Regex::new(pattern).map(Self)
+    }
+
+    /// A glob-like pattern, where `*` matches as few characters as it can and
+    /// everything else is literal.
+    pub fn from_glob(glob: &str) -> Self {
+        Self::from_source(&glob_source(glob, ".*?"))
+    }
+
+    /// A glob that has to match the whole text, not just part of it.
+    pub fn from_full_glob(glob: &str) -> Self {
+        Self::from_source(&format!("^(?:{})$", glob_source(glob, ".*")))
+    }
+
+    fn from_source(source: &str) -> Self {
+        Self(Regex::new(source).expect("glob is escaped"))
+    }
+
+    pub fn is_match(&self, text: &str) -> bool {
+        self.0.is_match(text)
+    }
+
+    /// Replaces every match, with `$1` or `${1}` in the replacement standing
+    /// for the groups and `$$` for a dollar sign.
+    pub fn replace_all(&self, text: &str, replacement: &str) -> String {
+        self.0.replace_all(text, replacement).into_owned()
+    }
+}
+
+impl PartialEq for Pattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.as_str() == other.0.as_str()
+    }
+}
+
+impl Eq for Pattern {}
+
+fn glob_source(glob: &str, wildcard: &str) -> String {
+    glob.split('*').map(regex::escape).collect::<Vec<_>>().join(wildcard)
+}
+
+#[test]
+fn test_pattern_replace() {
+    let replace = |pattern: &str, text: &str, replacement: &str| Pattern::new(pattern).unwrap().replace_all(text, replacement);
+
+    assert_eq!(replace(r"\s*[\(\[](Official|Lyric) Video[\)\]]", "Song [Official Video]", ""), "Song");
+    assert_eq!(replace(r" - (\d{4} )?Remaster(ed)?$", "Song - 2011 Remaster", ""), "Song");
+    assert_eq!(replace(r"(\w+), (\w+)", "Bach, Johann", "$2 $1"), "Johann Bach");
+    assert_eq!(replace(r"<.+?>", "<a>b<c>", ""), "b");
+    assert_eq!(replace(r"x*", "ab", "-"), "-a-b-");
+    assert_eq!(replace(r"^", "ab", "$$"), "$ab");
+    assert_eq!(replace(r"[^a-c]+", "abxyc", "_"), "ab_c");
+
+    // Patterns that took exponential time to backtrack
+    let long = format!("{}b", "a".repeat(64));
+    assert_eq!(replace(r"(a+)+$", &long, ""), long);
+    assert_eq!(replace(r"(a|aa)*c", &long, ""), long);
+
+    assert_eq!(
+        Pattern::from_glob(" (Remastered *)").replace_all("Song (Remastered 2011) (Live)", ""),
+        "Song (Live)",
+    );
+    assert_eq!(Pattern::from_glob("a.b").replace_all("a.b axb", ""), " axb");
+
+    let glob = Pattern::from_full_glob("org.mpris.mediaplayer2.firefox*");
+    assert!(glob.is_match("org.mpris.mediaplayer2.firefox.instance_1_42"));
+    assert!(!glob.is_match("org.mpris.mediaplayer2.spotify"));
+    assert!(!Pattern::from_full_glob("mpv").is_match("mpv2"));
+    assert!(!Pattern::from_full_glob("mpv").is_match("xmpv"));
+
+    assert_eq!(Pattern::from_glob("a*"), Pattern::from_glob("a*"));
+    assert_ne!(Pattern::from_glob("a*"), Pattern::from_full_glob("a*"));
+
+    assert!(Pattern::new("(a").is_err());
+    assert!(Pattern::new("a)").is_err());
+    assert!(Pattern::new("*a").is_err());
+    assert!(Pattern::new("a{2,1}").is_err());
+    assert!(Pattern::new("[z-a]").is_err());
+}